@@ -0,0 +1,53 @@
+// Measures how each difficulty does against the random player and against 
+// perfect play, as the share of points it takes (a draw is half a point).
+//
+//   cargo run --release --no-default-features --example difficulty_harness -- [pairs] [opening]
+//
+// Games come in pairs from the same random opening with colours swapped. 
+// Games against the solver start from a later opening (14 moves by default),
+// since it has to solve every move it plays.
+
+use std::env;
+use std::time::Instant;
+
+use connect_four::ai::{self, solver::Solver, random::Random};
+use connect_four::engine::play_match;
+use connect_four::{AIType, EngineKind};
+
+fn arg<T: std::str::FromStr>(index: usize, default: T) -> T {
+    env::args().nth(index).and_then(|a| a.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let pairs: u32 = arg(1, 10);
+    let opening: i8 = arg(2, 14);
+    quad_rand::srand(1);
+
+    println!("| level | vs random | vs solver | time |");
+    println!("|---|---|---|---|");
+
+    let levels = [
+        (EngineKind::AlphaBeta, AIType::Beginner), 
+        (EngineKind::AlphaBeta, AIType::Easy), 
+        (EngineKind::AlphaBeta, AIType::Medium), 
+        (EngineKind::AlphaBeta, AIType::Hard), 
+        (EngineKind::AlphaBeta, AIType::Impossible), 
+        (EngineKind::MonteCarlo, AIType::Hard)
+    ];
+
+    for (kind, ai_type) in levels {
+        let start = Instant::now();
+        let mut engine = ai::new_engine(kind, ai_type, None, 1);
+        let random = play_match(engine.as_mut(), &mut Random::new(), pairs, 2);
+        let solver = play_match(engine.as_mut(), &mut Solver::new(), pairs, opening);
+
+        println!(
+            "| {:?} {:?} | {:.0}% | {:.0}% | {:.1?} |", 
+            kind,
+            ai_type, 
+            100. * random.score(), 
+            100. * solver.score(), 
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,47 @@
+// Counts the nodes the solver needs for a fixed set of positions with each
+// combination of move ordering heuristics.
+//
+//   cargo run --release --no-default-features --example move_ordering_bench -- [moves] [positions]
+
+use std::env;
+use std::time::Instant;
+
+use connect_four::ai::alpha_beta::MoveOrdering;
+use connect_four::ai::solver::Solver;
+use connect_four::engine::random_opening;
+
+fn arg<T: std::str::FromStr>(index: usize, default: T) -> T {
+    env::args().nth(index).and_then(|a| a.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let moves: i8 = arg(1, 18);
+    let count: usize = arg(2, 20);
+
+    quad_rand::srand(4);
+    let positions: Vec<_> = (0..count).map(|_| random_opening(moves)).collect();
+
+    let orderings = [
+        ("centre out", MoveOrdering::none()),
+        ("threats", MoveOrdering::default()),
+        ("threats + killers", MoveOrdering { killers: true, ..MoveOrdering::default() }),
+        ("threats + history", MoveOrdering { history: true, ..MoveOrdering::default() }),
+        ("all", MoveOrdering { threats: true, killers: true, history: true }),
+    ];
+
+    println!("| ordering | nodes | time |");
+    println!("|---|---|---|");
+
+    let mut expected: Option<Vec<i8>> = None;
+    for (name, ordering) in orderings {
+        let mut solver = Solver::new();
+        solver.set_move_ordering(ordering);
+
+        let start = Instant::now();
+        let scores: Vec<i8> = positions.iter().map(|board| solver.solve(board)).collect();
+        let elapsed = start.elapsed();
+
+        assert_eq!(&scores, expected.get_or_insert(scores.clone()), "ordering changed a score");
+        println!("| {} | {} | {:.2?} |", name, solver.nodes_explored(), elapsed);
+    }
+}
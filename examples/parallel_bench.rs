@@ -0,0 +1,52 @@
+// Times the solver on random positions with 1, 2, 4... threads.
+//
+//   cargo run --release --no-default-features --example parallel_bench -- [moves] [positions] [threads]
+//
+// The positions are random openings like the solver tests use, but start 
+// earlier in the game (16 moves by default) so there is enough to search for
+// the threads to matter.
+
+use std::env;
+use std::time::{Duration, Instant};
+
+use connect_four::ai::solver::Solver;
+use connect_four::engine::random_opening;
+
+fn arg<T: std::str::FromStr>(index: usize, default: T) -> T {
+    env::args().nth(index).and_then(|a| a.parse().ok()).unwrap_or(default)
+}
+
+fn main() {
+    let moves: i8 = arg(1, 16);
+    let count: usize = arg(2, 10);
+    let max_threads: usize = arg(3, std::thread::available_parallelism().map_or(1, |n| n.get()));
+    quad_rand::srand(3);
+    let positions: Vec<_> = (0..count).map(|_| random_opening(moves)).collect();
+
+    println!("| threads | time | nodes | speedup |");
+    println!("|---|---|---|---|");
+
+    let mut baseline: Option<(Duration, Vec<i8>)> = None;
+    let mut threads = 1;
+    while threads <= max_threads {
+        // a fresh table for each run so earlier runs don't help
+        let mut solver = Solver::new();
+        solver.set_threads(threads);
+
+        let start = Instant::now();
+        let scores: Vec<i8> = positions.iter().map(|board| solver.solve(board)).collect();
+        let elapsed = start.elapsed();
+
+        let (base_time, base_scores) = baseline.get_or_insert((elapsed, scores.clone()));
+        assert_eq!(&scores, base_scores, "threads changed a score");
+        println!(
+            "| {} | {:.2?} | {} | {:.2}x |", 
+            threads, 
+            elapsed, 
+            solver.nodes_explored(), 
+            base_time.as_secs_f64() / elapsed.as_secs_f64()
+        );
+
+        threads *= 2;
+    }
+}
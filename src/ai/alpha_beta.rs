@@ -1,33 +1,345 @@
-// use std::time::Instant;
-use macroquad::rand;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use quad_rand as rand;
 
-use crate::board::Board;
+use crate::board::{Board, Outcome};
+use crate::ai::evaluation::{evaluate, Weights};
+use crate::ai::stats::SearchStats;
+use crate::book::OpeningBook;
 use crate::AIType;
-use crate::board::{I_HEIGHT, I_WIDTH, MIN_SCORE};
-use crate::transition_table::TransitionTable;
+use crate::board::{I_HEIGHT, I_WIDTH, S_HEIGHT, S_WIDTH};
+use crate::engine::Engine;
+use crate::transition_table::{Bound, TableSize, TransitionTable};
+
+fn last_move(board: &Board) -> u8 {
+    *board.moves_played().last().unwrap()
+}
+
+// Nodes searched per call to Engine::step, few enough to fit in a frame
+const STEP_NODES: u128 = 10_000;
+
+// Mirror a column unless the board is on the canonical side. Table entries
+// keep their best move as seen from the canonical side.
+fn flip_move(col: u8, canonical: bool) -> u8 {
+    if canonical { col } else { S_WIDTH as u8 - 1 - col }
+}
+
+// How much work a search may do. Iterative deepening goes up to max_depth but 
+// stops early once the time or node budget runs out, keeping the result of 
+// the last completed iteration.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SearchLimits {
+    pub max_depth: u8,
+    pub time: Option<Duration>,
+    pub nodes: Option<u128>,
+}
+
+impl SearchLimits {
+    pub fn depth(max_depth: u8) -> Self {
+        SearchLimits { max_depth, time: None, nodes: None }
+    }
+}
+
+// Search limits used for each difficulty. There is no clock on the web, so 
+// the browser build gets a node budget of roughly the same size instead.
+pub fn limits(ai_type: AIType) -> SearchLimits {
+    let (max_depth, millis): (u8, Option<u64>) = match ai_type {
+        AIType::Beginner => (1, None),
+        AIType::Easy => (4, None),
+        AIType::Medium => (10, Some(250)),
+        AIType::Hard => (17, Some(1000)),
+        AIType::Impossible => (42, Some(5000)),
+    };
+
+    if cfg!(target_arch = "wasm32") {
+        SearchLimits { max_depth, time: None, nodes: millis.map(|m| m as u128 * 2_000) }
+    } else {
+        SearchLimits { max_depth, time: millis.map(Duration::from_millis), nodes: None }
+    }
+}
+
+// How far each difficulty strays from the best move, see sample_move(). 
+// Scores are in the units of the search, where 1 is roughly a disc's worth of
+// a win or an open three.
+pub fn temperature(ai_type: AIType) -> f32 {
+    match ai_type {
+        AIType::Easy => 3.,
+        AIType::Medium => 1.,
+        // Beginner plays at random rather than searching, see new_engine()
+        AIType::Beginner | AIType::Hard | AIType::Impossible => 0.,
+    }
+}
+
+// Pick a move with probability proportional to exp(score / temperature), so 
+// moves a little worse than the best are played often and much worse ones 
+// rarely. A temperature of 0 always picks the first best move.
+pub fn sample_move(scores: &[i8], temperature: f32) -> usize {
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if temperature <= 0. {
+        return scores.iter().position(|s| *s == best).unwrap_or(0);
+    }
+
+    let weights: Vec<f32> = scores.iter()
+        .map(|s| ((*s - best) as f32 / temperature).exp())
+        .collect();
+
+    let mut target = rand::RandomRange::gen_range(0., weights.iter().sum::<f32>());
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+
+    // only reached through rounding
+    scores.len().saturating_sub(1)
+}
+
+// Heuristics that sort the moves at each node, after the table's best move.
+// Moves that tie keep the centre-out order.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MoveOrdering {
+    pub threats: bool, // moves that make more new threats first
+    pub killers: bool, // the last move to cause a cutoff at the same ply
+    pub history: bool, // moves that have caused cutoffs elsewhere in the tree
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering { threats: true, killers: false, history: false }
+    }
+}
+
+impl MoveOrdering {
+    // Centre-out only
+    pub fn none() -> Self {
+        MoveOrdering { threats: false, killers: false, history: false }
+    }
+}
+
+// An iterative deepening search in progress, see AlphaBeta::start_search()
+pub struct Search {
+    board: Board,
+    boards: [Option<Board>; S_WIDTH],
+    max_depth: u8,
+    depth: u8,
+    iteration: Vec<i8>, // scores of the root moves searched so far at depth
+    scores: Vec<i8>, // scores from the last completed iteration
+    forced: Option<(Board, i8)> // set when there is nothing to search, with its score
+}
+
+impl Search {
+    pub fn is_done(&self) -> bool {
+        self.forced.is_some() || self.depth >= self.max_depth
+    }
+}
 
 pub struct AlphaBeta {
-    transposition_table: TransitionTable,
-    nodes_explored: u128
+    transposition_table: Arc<TransitionTable>, // shared with helper threads
+    threads: usize,
+    stats: SearchStats,
+    ai_type: AIType,
+    limits: SearchLimits,
+    weights: Weights, // for positions at the search horizon
+    ordering: MoveOrdering,
+    killers: [Option<u8>; S_WIDTH*S_HEIGHT + 1], // indexed by counter
+    history: [[u64; 64]; 2], // by player and the bit of the move
+
+    // state of the current search's budget
+    deadline: Option<Instant>,
+    node_budget: Option<u128>,
+    budget_active: bool,
+    aborted: bool,
+    started: Option<Instant>,
+    stop: Option<Arc<AtomicBool>>,
+    book: Option<Arc<OpeningBook>>,
+
+    // what the last search expects to happen
+    score: i8,
+    principal_variation: Vec<u8>,
+
+    // search started through Engine::step, which gives up after a number of 
+    // nodes and carries on from the table next time
+    pending: Option<Search>,
+    step_end: Option<u128>,
+    yielded: bool
+}
+
+impl Default for AlphaBeta {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl AlphaBeta {
     pub fn new() -> Self {
-        AlphaBeta { transposition_table: TransitionTable::new(), nodes_explored: 0}
+        Self::with_difficulty(AIType::Hard)
+    }
+
+    pub fn with_difficulty(ai_type: AIType) -> Self {
+        AlphaBeta { 
+            transposition_table: Arc::new(TransitionTable::new()), 
+            threads: 1,
+            stats: SearchStats::default(),
+            ai_type,
+            limits: limits(ai_type),
+            weights: Weights::default(),
+            ordering: MoveOrdering::default(),
+            killers: [None; S_WIDTH*S_HEIGHT + 1],
+            history: [[0; 64]; 2],
+            deadline: None,
+            node_budget: None,
+            budget_active: false,
+            aborted: false,
+            started: None,
+            score: 0,
+            principal_variation: Vec::new(),
+            stop: None,
+            book: None,
+            pending: None,
+            step_end: None,
+            yielded: false
+        }
+    }
+
+    // Override the limits used when playing as an Engine
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    // Sort key for a move, higher is searched first
+    fn move_priority(&self, board: &mut Board, col: usize, first_move: Option<u8>) -> u64 {
+        if first_move == Some(col as u8) {
+            return u64::MAX;
+        }
+
+        // threats come first, killers break ties between them and the history
+        // breaks any ties left
+        let player = board.side_to_move();
+        let mut priority = 0;
+        if self.ordering.history {
+            priority += self.history[player.index()][board.next_cell(col)].min((1 << 39) - 1);
+        }
+        if self.ordering.killers && self.killers[board.counter as usize] == Some(col as u8) {
+            priority += 1 << 39;
+        }
+        if self.ordering.threats {
+            board.make_move(col);
+            priority += (board.threats(player).count_ones() as u64) << 40;
+            board.undo_move();
+        }
+
+        priority
     }
 
-    fn negamax(&mut self, board: &Board, depth: u8, alpha: i8, beta: i8) -> i8 {
-        self.nodes_explored += 1;
+    fn record_cutoff(&mut self, board: &Board, col: usize, depth: u8) {
+        if self.ordering.killers {
+            self.killers[board.counter as usize] = Some(col as u8);
+        }
+        if self.ordering.history {
+            let player = board.side_to_move().index();
+            self.history[player][board.next_cell(col)] += depth as u64 * depth as u64;
+        }
+    }
 
-        // Check if game is drawn or if the search is at max depth
-        if board.is_draw() || depth == 0{
+    // Search with this many threads in native builds. The extra threads 
+    // search the same position in a different order and share the table, so
+    // the main search finds more of the tree already solved (lazy SMP). Only
+    // the main search picks the move. It gets the same score as with one 
+    // thread, but may pick a different move with that score.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    // Replace the table with an empty one of a different size
+    pub fn set_table_size(&mut self, size: TableSize) {
+        self.transposition_table = Arc::new(TransitionTable::with_size(size));
+    }
+
+    // Keep what the table has learned between runs. Entries from a depth
+    // limited search are only right for the same evaluation weights.
+    pub fn save_table<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.transposition_table.save(path)
+    }
+
+    pub fn load_table<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.transposition_table = Arc::new(TransitionTable::load(path)?);
+        Ok(())
+    }
+
+    pub fn set_move_ordering(&mut self, ordering: MoveOrdering) {
+        self.ordering = ordering;
+    }
+
+    // Change how positions at the search horizon are scored. This clears the
+    // table, since the stored scores came from the old weights.
+    pub fn set_weights(&mut self, weights: Weights) {
+        self.weights = weights;
+        self.clear();
+    }
+
+    // Play straight from the opening book while the position is in it
+    pub fn set_book(&mut self, book: Arc<OpeningBook>) {
+        self.book = Some(book);
+    }
+
+    fn out_of_budget(&self) -> bool {
+        if self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed)) {
+            return true;
+        }
+
+        if !self.budget_active {
+            return false;
+        }
+
+        let out_of_nodes = self.node_budget.is_some_and(|n| self.stats.nodes >= n);
+
+        // checking the clock every node is slow, so only do it now and then
+        let out_of_time = self.stats.nodes & 1023 == 0 
+            && self.deadline.is_some_and(|d| Instant::now() >= d);
+
+        out_of_nodes || out_of_time
+    }
+
+    // Assumes the side to move can not win immediately, which holds for every
+    // board returned by get_next_non_losing_boards(). Moves are made and taken
+    // back on the board, which is left as it was.
+    pub(crate) fn negamax(&mut self, board: &mut Board, depth: u8, alpha: i8, beta: i8) -> i8 {
+        self.stats.nodes += 1;
+        if self.out_of_budget() {
+            self.aborted = true;
+            return 0;
+        }
+
+        if self.step_end.is_some_and(|end| self.stats.nodes >= end) {
+            self.aborted = true;
+            self.yielded = true;
             return 0;
         }
+
+        // Check if game is drawn or if the search is at max depth, where the
+        // position gets a heuristic score instead
+        if board.is_draw() {
+            return 0;
+        }
+
+        if depth == 0 {
+            return evaluate(board, &self.weights);
+        }
         
-        // Get boards where the next move is not an immediate loss and if there
-        // are no boards than return a negative evaluation
-        let boards = board.get_next_non_losing_boards();
-        if boards[0].is_none() {
+        // Get moves that are not an immediate loss and if there are none then
+        // return a negative evaluation
+        let columns = board.non_losing_columns();
+        if columns[0].is_none() {
             return -(I_WIDTH*I_HEIGHT - board.counter)/2
         }
 
@@ -44,125 +356,728 @@ impl AlphaBeta {
 
         // Update beta, upper bound, if beta is greater than the max possible score and prune
         // if alpha is greater than beta.
-        let max: i8 = match self.transposition_table.get(board.hash()) {
-            Some(val) => val + MIN_SCORE - 1,
-            None => (I_WIDTH*I_HEIGHT- 1 - board.counter)/2,
-        };
-
         let mut b = beta;
-        if beta > max {
+        let max = (I_WIDTH*I_HEIGHT - 1 - board.counter)/2;
+        if b > max {
             b = max;
-            if alpha >= b { 
+            if a >= b { 
                 return b; 
             }
         }
 
-        // Run negamax
-        for next_board in boards.iter().flatten() {
-            let s = -self.negamax(next_board, depth - 1, -beta, -a);
-            
-            if s >= b { 
-                return s; 
+        // Only reuse results searched to the same depth, so a depth limited 
+        // search gives the same answer however warm the table is. Any entry is
+        // still good for trying its best move first. A position and its 
+        // mirror share an entry, so the move is stored for the canonical side.
+        let (key, canonical) = board.canonical();
+        let mut first_move = None;
+        self.stats.tt_probes += 1;
+        let entry = self.transposition_table.get(key);
+        if entry.is_none() && self.transposition_table.occupied(key) {
+            self.stats.tt_collisions += 1;
+        }
+
+        if let Some(entry) = entry {
+            self.stats.tt_hits += 1;
+            if entry.depth == depth {
+                match entry.bound {
+                    Bound::Exact => return entry.val,
+                    Bound::Lower if entry.val > a => a = entry.val,
+                    Bound::Upper if entry.val < b => b = entry.val,
+                    _ => {}
+                }
+
+                if a >= b {
+                    return entry.val;
+                }
+            }
+
+            first_move = Some(flip_move(entry.best_move, canonical));
+        }
+
+        // Run negamax, starting with the stored best move if there is one and
+        // then by the move ordering heuristics
+        let original_a = a;
+        let mut best_score = -(I_WIDTH*I_HEIGHT);
+        let mut best_move = 0;
+        let mut order = [(0, 0); S_WIDTH];
+        let mut len = 0;
+        for col in columns.into_iter().map_while(|col| col) {
+            order[len] = (self.move_priority(board, col, first_move), col);
+            len += 1;
+        }
+        order[..len].sort_by_key(|(priority, _)| std::cmp::Reverse(*priority));
+
+        for &(_, col) in &order[..len] {
+            board.make_move(col);
+            let s = -self.negamax(board, depth - 1, -b, -a);
+            board.undo_move();
+            if self.aborted {
+                // the search is being thrown away, so don't store anything
+                return 0;
+            }
+
+            if s > best_score {
+                best_score = s;
+                best_move = col as u8;
             }
             if s > a { 
                 a = s; 
             }
+            if a >= b { 
+                self.stats.cutoffs += 1;
+                self.record_cutoff(board, col, depth);
+                break;
+            }
         }
 
         // update transposition table
-        self.transposition_table.set(board.hash(), a - MIN_SCORE + 1);
+        let bound = if best_score <= original_a {
+            Bound::Upper
+        } else if best_score >= b {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.transposition_table.set(key, best_score, depth, bound, flip_move(best_move, canonical));
 
-        // return alpha
-        a
+        best_score
     }
 
+    // Forget everything learned in previous searches
+    pub fn clear(&mut self) {
+        self.transposition_table.reset();
+    }
 
-    pub fn make_move(&mut self, board: &mut Board, max_depth: u8, ai_type: &AIType) {
-        let boards = board.get_next_non_losing_boards();
-        let mut game_ending_move_found = false;
-        let mut index = 0;
+    // Number of positions visited by the last call to make_move
+    pub fn nodes_explored(&self) -> u128 {
+        self.stats.nodes
+    }
 
-        // If there is more than one possible none losing move, than we go
-        // through the search process
-        if boards[0].is_some() {
-            // let time = Instant::now();
+    // Depth of the last iteration make_move completed, counted from the root
+    pub fn depth_reached(&self) -> u8 {
+        self.stats.depth_reached
+    }
 
-            // Check if there are any moves that end the game. If so, use that and
-            // avoid wasted computation in the search
-            for (i, wrapped_board) in boards.iter().enumerate() {
-                if let Some(b) = wrapped_board {
-                    if b.is_game_over(b.bit_board[1]) {
-                        index = i;
-                        game_ending_move_found = true;
-                        break;
-                    }
-                }
+    // Counters from the last search
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    // Score of the last move chosen, from the point of view of the side that
+    // played it
+    pub fn score(&self) -> i8 {
+        self.score
+    }
+
+    // Columns the last search expects to be played, starting with its own 
+    // move
+    pub fn principal_variation(&self) -> &[u8] {
+        &self.principal_variation
+    }
+
+    // Follow the best moves stored in the table from the position after the 
+    // chosen move, as far as the search looked. Only exact entries are on the
+    // line, the move stored with a bound is just the first one to cut off or 
+    // the best of a fail low.
+    fn record_line(&mut self, chosen: &Board, score: i8) {
+        self.score = score;
+        self.principal_variation.clear();
+        self.principal_variation.push(last_move(chosen));
+
+        let mut board = *chosen;
+        while self.principal_variation.len() < self.stats.depth_reached as usize 
+            && board.outcome() == Outcome::InProgress 
+        {
+            let (key, canonical) = board.canonical();
+            let entry = self.transposition_table.get(key)
+                .filter(|entry| entry.bound == Bound::Exact);
+            match entry.map(|entry| flip_move(entry.best_move, canonical)) {
+                Some(col) if board.can_play(col as usize) => {
+                    board.make_move(col as usize);
+                    self.principal_variation.push(col);
+                },
+                _ => break
             }
+        }
+    }
 
-            // Otherwise, go through the search process
-            if !game_ending_move_found {
-                let mut best_score = -(I_WIDTH*I_HEIGHT);
-                let mut scores = Vec::new();
-
-                // Evaluate possible moves with iterative deepening search
-                let min = -(I_WIDTH*I_HEIGHT - board.counter)/2;
-                let max = (I_WIDTH*I_HEIGHT + 1 - board.counter)/2;
-                
-                // Iterative deepening, starting at a reasonable depth
-                for depth in (max_depth/3)..max_depth {
-                    scores.clear();
-                    for b in boards.iter().flatten() {
-                        scores.push(-self.negamax(
-                            b, 
-                            depth, 
-                            min,
-                            max
-                        ));
-                    }
-                }
+    // A searcher for a helper thread, sharing this one's table
+    fn helper(&self, stop: Arc<AtomicBool>) -> AlphaBeta {
+        AlphaBeta {
+            transposition_table: self.transposition_table.clone(),
+            limits: self.limits,
+            weights: self.weights,
+            ordering: self.ordering,
+            stop: Some(stop),
+            ..Self::with_difficulty(self.ai_type)
+        }
+    }
+
+    // Search every move from the board at each depth until stopped, starting
+    // from a different move for each helper. Returns the nodes visited.
+    fn help(&mut self, board: &Board, depths: Range<u8>, id: usize) -> u128 {
+        let mut boards: Vec<Board> = board.get_next_non_losing_boards().into_iter().flatten().collect();
+        if boards.is_empty() {
+            return 0;
+        }
 
-                // RNG added to make easy and medium bots easier to defeat
-                if *ai_type == AIType::Easy || *ai_type == AIType::Medium {
-                    // Choose move probabilistically 
-                    let sum = scores.iter().sum::<i8>() as f32;
-                    let rand = rand::RandomRange::gen_range(0., 1.);
-                    let mut current_probability = 0.;
-                    for (i, s) in scores.iter().enumerate() {
-                        current_probability += (*s as f32) / sum;
-                        if current_probability >= rand {
-                            index = i;
-                            break;
-                        }
-                    }
-                } else {
-                    // Choose the best move
-                    for (i, s) in scores.iter().enumerate() {
-                        if *s > best_score {
-                            best_score = *s;
-                            index = i;
-                        }
-                    }
+        let len = boards.len();
+        boards.rotate_left(id % len);
+        let min = -(I_WIDTH*I_HEIGHT - board.counter)/2;
+        let max = (I_WIDTH*I_HEIGHT + 1 - board.counter)/2;
+        'search: for depth in depths {
+            for b in &mut boards {
+                self.negamax(b, depth, min, max);
+                if self.aborted {
+                    break 'search;
                 }
+            }
+        }
+
+        self.stats.nodes
+    }
 
-                // Log some simple stats formatted for a markdown table
-                // let elapsed = time.elapsed();
-                // println!("| {} | {:?} | {} |", 
-                //     self.nodes_explored, 
-                //     elapsed, 
-                //     self.nodes_explored as f32  / (1000. * elapsed.as_secs_f32()));
-
-                // Clear transposition table since it is no longer accurate with a 
-                // depth limited approach
-                self.transposition_table.reset();
-                self.nodes_explored = 0;
+    // Run the search on this thread while helper threads search the moves 
+    // from the board at the given depths, and stop them once it is done.
+    // Their nodes are added to this search's count.
+    fn with_helpers<R>(&mut self, board: &Board, depths: Range<u8>, search: impl FnOnce(&mut Self) -> R) -> R {
+        if self.threads <= 1 || cfg!(target_arch = "wasm32") {
+            return search(self);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let helpers: Vec<AlphaBeta> = (1..self.threads).map(|_| self.helper(stop.clone())).collect();
+        thread::scope(|scope| {
+            let handles: Vec<_> = helpers.into_iter()
+                .enumerate()
+                .map(|(i, mut helper)| {
+                    let depths = depths.clone();
+                    scope.spawn(move || helper.help(board, depths, i + 1))
+                })
+                .collect();
+
+            let result = search(self);
+            stop.store(true, Ordering::Relaxed);
+            for handle in handles {
+                self.stats.nodes += handle.join().expect("helper thread panicked");
             }
 
-            // update the board
-            std::mem::swap(board, &mut (boards[index].unwrap()));
+            result
+        })
+    }
+
+    // Solve the position exactly with all the threads, see Solver
+    pub(crate) fn solve(&mut self, board: &Board, alpha: i8, beta: i8) -> i8 {
+        let depth = (I_WIDTH*I_HEIGHT - board.counter) as u8;
+        let mut root = *board;
+        self.with_helpers(board, depth - 1..depth, |alpha_beta| alpha_beta.negamax(&mut root, depth, alpha, beta))
+    }
+
+    pub fn make_move(&mut self, board: &mut Board, max_depth: u8) -> SearchStats {
+        self.make_move_with_limits(board, SearchLimits::depth(max_depth))
+    }
+
+    pub fn make_move_with_limits(&mut self, board: &mut Board, limits: SearchLimits) -> SearchStats {
+        let mut search = self.start_search(board, limits);
+        if !search.is_done() {
+            let depths = search.depth..search.max_depth;
+            self.with_helpers(board, depths, |alpha_beta| {
+                while !alpha_beta.step_search(&mut search) {}
+            });
+        }
+        *board = self.finish_search(search);
+        self.stats
+    }
+
+    // Set up a search that step_search() runs one root move at a time, so it
+    // can be spread over several frames when there are no threads. Engine::step
+    // also caps the nodes of each step, see STEP_NODES.
+    pub fn start_search(&mut self, board: &Board, limits: SearchLimits) -> Search {
+        self.stats = SearchStats::default();
+        self.killers = [None; S_WIDTH*S_HEIGHT + 1];
+        self.history = [[0; 64]; 2];
+        self.started = if cfg!(target_arch = "wasm32") { None } else { Some(Instant::now()) };
+
+        // The budget only applies once the first iteration is done, so 
+        // there is always a result to fall back on. That iteration only looks
+        // one move ahead, so it is over straight away.
+        self.node_budget = limits.nodes;
+        self.deadline = limits.time.map(|t| Instant::now() + t);
+        self.budget_active = false;
+        self.aborted = false;
+
+        let mut search = Search {
+            board: *board,
+            boards: board.get_next_non_losing_boards(),
+            max_depth: limits.max_depth,
+            depth: 0, // of the moves after the root
+            iteration: Vec::new(),
+            scores: Vec::new(),
+            forced: None
+        };
+
+        // Check if there are any moves that win the game. If so, use that and
+        // avoid wasted computation in the search. This has to look at every 
+        // move since a winning move can leave the opponent with a "threat" that
+        // get_next_non_losing_boards() would filter out.
+        let player = board.side_to_move();
+        for b in board.get_next_boards() {
+            if b.outcome() == Outcome::Won(player) {
+                search.forced = Some((b, (I_WIDTH*I_HEIGHT + 1 - board.counter)/2));
+                return search;
+            }
+        }
+
+        if let Some(col) = self.book.as_ref().and_then(|book| book.best_move(board)) {
+            let mut next = *board;
+            next.make_move(col);
+            let score = self.book.as_ref().and_then(|book| book.get(&next)).map_or(0, |s| -s);
+            search.forced = Some((next, score));
+            return search;
+        }
+
+        // If there isn't a non-losing move, we select a random board to keep 
+        // the game going
+        if search.boards[0].is_none() {
+            let score = -(I_WIDTH*I_HEIGHT - board.counter)/2;
+            search.forced = Some((board.get_next_boards()[0], score));
+        }
+
+        search
+    }
+
+    // Search the next root move, or as much of it as the step's nodes allow.
+    // Returns true once the search is finished.
+    pub fn step_search(&mut self, search: &mut Search) -> bool {
+        if search.is_done() {
+            return true;
+        }
+
+        // Evaluate possible moves with iterative deepening search
+        let counter = search.board.counter;
+        let min = -(I_WIDTH*I_HEIGHT - counter)/2;
+        let max = (I_WIDTH*I_HEIGHT + 1 - counter)/2;
+        let mut b = search.boards[search.iteration.len()].unwrap();
+        let score = -self.negamax(&mut b, search.depth, min, max);
+
+        // Out of nodes for this step. The move is searched again next step, 
+        // finding the parts it already finished in the table.
+        if self.yielded {
+            self.yielded = false;
+            self.aborted = false;
+            return false;
+        }
+
+        search.iteration.push(score);
+        if self.aborted {
+            search.depth = search.max_depth;
+            return true;
+        }
+
+        // Iteration finished, deepen
+        if search.boards.get(search.iteration.len()).is_none_or(|b| b.is_none()) {
+            search.scores = std::mem::take(&mut search.iteration);
+            self.stats.depth_reached = search.depth + 1;
+            self.budget_active = true;
+            search.depth += 1;
+
+            // no point going deeper than the end of the game
+            if search.depth as i8 > I_WIDTH*I_HEIGHT - counter {
+                search.depth = search.max_depth;
+            }
+        }
+
+        search.is_done()
+    }
+
+    // Pick the move from the last completed iteration
+    pub fn finish_search(&mut self, search: Search) -> Board {
+        self.budget_active = false;
+        if let Some(started) = self.started {
+            self.stats.elapsed = started.elapsed();
+        }
+
+        if let Some((board, score)) = search.forced {
+            self.record_line(&board, score);
+            return board;
+        }
+
+        // RNG added to make the easier bots easier to defeat
+        let scores = search.scores;
+        let index = sample_move(&scores, temperature(self.ai_type));
+        let board = search.boards[index].unwrap();
+        // a search stopped during its first iteration has no scores
+        self.record_line(&board, scores.get(index).copied().unwrap_or(0));
+        board
+    }
+}
+
+impl Engine for AlphaBeta {
+    fn choose_move(&mut self, board: &Board) -> usize {
+        let mut next = *board;
+        self.make_move_with_limits(&mut next, self.limits);
+        last_move(&next) as usize
+    }
+
+    fn step(&mut self, board: &Board) -> Option<usize> {
+        let mut search = match self.pending.take() {
+            Some(search) if search.board.moves_played() == board.moves_played() => search,
+            _ => self.start_search(board, self.limits),
+        };
+
+        self.step_end = Some(self.stats.nodes + STEP_NODES);
+        let done = self.step_search(&mut search);
+        self.step_end = None;
+        if done {
+            Some(last_move(&self.finish_search(search)) as usize)
         } else {
-            // Else there isn't a non-losing, and we select a random board to 
-            // keep the game going
-            std::mem::swap(board, &mut board.get_next_boards()[0]);
+            self.pending = Some(search);
+            None
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    fn set_stop_signal(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    fn principal_variation(&self) -> Vec<u8> {
+        self.principal_variation.clone()
+    }
+
+    fn stats(&self) -> Option<SearchStats> {
+        Some(self.stats)
+    }
+
+    // Only the exact values, to keep the file small enough to write between 
+    // games
+    fn save(&self, path: &Path) -> io::Result<()> {
+        self.transposition_table.save_exact(path)
+    }
+
+    fn load(&mut self, path: &Path) -> io::Result<()> {
+        self.load_table(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn play(moves: &str) -> usize {
+        let mut alpha_beta = AlphaBeta::new();
+        let mut board = Board::from_moves(moves).unwrap();
+        alpha_beta.make_move(&mut board, 8);
+        *board.moves_played().last().unwrap() as usize
+    }
+
+    #[test]
+    fn test_takes_win_as_either_colour() {
+        // white to move with three stacked in the first column
+        assert_eq!(play("121212"), 0);
+
+        // red to move with three stacked in the second column, white's three 
+        // in the first column are already blocked
+        assert_eq!(play("1212127"), 1);
+    }
+
+    #[test]
+    fn test_blocks_as_either_colour() {
+        // white must stop red in the second column
+        assert_eq!(play("121272"), 1);
+
+        // red must stop white in the second column
+        assert_eq!(play("21212"), 1);
+    }
+
+    #[test]
+    fn test_table_survives_between_moves() {
+        // positions from one game, searched in order as the game would
+        let game = "4453635526";
+        let depth = 10;
+
+        let mut cold = AlphaBeta::new();
+        let mut warm = AlphaBeta::new();
+        let mut cold_nodes = 0;
+        let mut warm_nodes = 0;
+        for plies in 0..game.len() {
+            let board = Board::from_moves(&game[..plies]).unwrap();
+
+            cold.clear();
+            let mut cold_board = board;
+            cold.make_move(&mut cold_board, depth);
+            cold_nodes += cold.nodes_explored();
+
+            let mut warm_board = board;
+            warm.make_move(&mut warm_board, depth);
+            warm_nodes += warm.nodes_explored();
+
+            assert_eq!(cold_board.moves_played(), warm_board.moves_played());
+        }
+
+        assert!(warm_nodes < cold_nodes, "warm {} vs cold {}", warm_nodes, cold_nodes);
+    }
+
+    #[test]
+    fn test_node_budget_keeps_last_completed_iteration() {
+        let board = Board::from_moves("4453").unwrap();
+        let limits = SearchLimits { max_depth: 42, time: None, nodes: Some(200_000) };
+
+        let mut limited = AlphaBeta::new();
+        let mut limited_board = board;
+        limited.make_move_with_limits(&mut limited_board, limits);
+        let depth = limited.depth_reached();
+        assert!(depth > 0 && depth < 42);
+
+        // the move matches a plain search to the depth that was completed
+        let mut full = AlphaBeta::new();
+        let mut full_board = board;
+        full.make_move(&mut full_board, depth);
+        assert_eq!(full.depth_reached(), depth);
+        assert_eq!(limited_board.moves_played(), full_board.moves_played());
+
+        // a budget too small for anything past the first iteration
+        let limits = SearchLimits { max_depth: 42, time: None, nodes: Some(10) };
+        let mut tiny = AlphaBeta::with_difficulty(AIType::Impossible);
+        let stats = tiny.make_move_with_limits(&mut Board::new(), limits);
+        assert_eq!(stats.depth_reached, 1);
+        assert!(stats.nodes <= 20, "{}", stats.nodes);
+    }
+
+    #[test]
+    fn test_time_budget() {
+        let limits = SearchLimits { max_depth: 42, time: Some(Duration::from_millis(50)), nodes: None };
+        let mut alpha_beta = AlphaBeta::new();
+        let mut board = Board::new();
+
+        let start = Instant::now();
+        alpha_beta.make_move_with_limits(&mut board, limits);
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(board.counter, 1);
+        assert!(alpha_beta.depth_reached() < 42);
+    }
+
+    #[test]
+    fn test_step_matches_choose_move() {
+        let board = Board::from_moves("4453").unwrap();
+        let limits = SearchLimits::depth(9);
+
+        let mut blocking = AlphaBeta::new();
+        blocking.set_limits(limits);
+        let expected = blocking.choose_move(&board);
+
+        let mut stepped = AlphaBeta::new();
+        stepped.set_limits(limits);
+        let mut steps = 1;
+        let mut nodes = 0;
+        let col = loop {
+            let step = stepped.step(&board);
+            // no step goes past its share of nodes
+            assert!(stepped.nodes_explored() - nodes <= STEP_NODES);
+            nodes = stepped.nodes_explored();
+            match step {
+                Some(col) => break col,
+                None => steps += 1,
+            }
+        };
+
+        assert!(steps > 1);
+        assert!(nodes > 2*STEP_NODES, "{}", nodes);
+        assert_eq!(col, expected);
+    }
+
+    #[test]
+    fn test_stop_signal() {
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut alpha_beta = AlphaBeta::new();
+        alpha_beta.set_limits(SearchLimits::depth(42));
+        alpha_beta.set_stop_signal(stop.clone());
+
+        let handle = std::thread::spawn(move || alpha_beta.choose_move(&Board::new()));
+        std::thread::sleep(Duration::from_millis(50));
+        stop.store(true, Ordering::Relaxed);
+
+        let start = Instant::now();
+        let col = handle.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(col < S_WIDTH);
+    }
+
+    #[test]
+    fn test_horizon_evaluation() {
+        // red is behind after white takes the centre, but only the heuristic
+        // can see that at this depth
+        let mut board = Board::from_moves("4").unwrap();
+        let mut alpha_beta = AlphaBeta::new();
+        assert!(alpha_beta.negamax(&mut board, 2, -21, 21) < 0);
+
+        alpha_beta.set_weights(Weights::zero());
+        assert_eq!(alpha_beta.negamax(&mut board, 2, -21, 21), 0);
+
+        // the moves searched are all taken back
+        assert_eq!(board.to_move_string(), "4");
+    }
+
+    #[test]
+    fn test_principal_variation() {
+        let mut alpha_beta = AlphaBeta::new();
+        let mut board = Board::from_moves("4").unwrap();
+        alpha_beta.make_move(&mut board, 8);
+
+        // the line starts with the move played and can be replayed from there
+        let line = alpha_beta.principal_variation().to_vec();
+        assert_eq!(line[0], last_move(&board));
+        assert!(line.len() > 1);
+        assert!(line.len() <= alpha_beta.depth_reached() as usize);
+
+        let mut replay = Board::from_moves("4").unwrap();
+        for col in &line {
+            if replay.counter > 1 {
+                let entry = alpha_beta.transposition_table.get(replay.canonical_key()).unwrap();
+                assert_eq!(entry.bound, Bound::Exact);
+            }
+            assert!(replay.make_move(*col as usize));
+        }
+
+        // an immediate win is the whole line
+        let mut board = Board::from_moves("121212").unwrap();
+        alpha_beta.make_move(&mut board, 8);
+        assert_eq!(alpha_beta.principal_variation(), &[0]);
+        assert_eq!(alpha_beta.score(), 18);
+        assert_eq!(crate::engine::format_line(alpha_beta.principal_variation()), "1");
+    }
+
+    #[test]
+    fn test_stats() {
+        let mut alpha_beta = AlphaBeta::new();
+        let mut board = Board::from_moves("4").unwrap();
+        let stats = alpha_beta.make_move(&mut board, 10);
+        assert_eq!(stats, alpha_beta.stats());
+        assert_eq!(stats.nodes, alpha_beta.nodes_explored());
+        assert_eq!(stats.depth_reached, 10);
+        assert!(stats.nodes > 0);
+        assert!(stats.elapsed > Duration::ZERO);
+        assert!(stats.tt_probes > 0 && stats.tt_hits <= stats.tt_probes);
+        assert!(stats.cutoffs > 0);
+
+        // counters start again with every search
+        let stats = alpha_beta.make_move(&mut Board::from_moves("121212").unwrap(), 10);
+        assert_eq!(stats.nodes, 0);
+        assert_eq!(stats.tt_probes, 0);
+    }
+
+    #[test]
+    fn test_threads_find_the_same_score() {
+        for moves in ["4", "4453", "3344556"] {
+            let mut single = AlphaBeta::new();
+            let mut board = Board::from_moves(moves).unwrap();
+            let single_stats = single.make_move(&mut board, 10);
+
+            let mut parallel = AlphaBeta::new();
+            parallel.set_threads(4);
+            let mut parallel_board = Board::from_moves(moves).unwrap();
+            let parallel_stats = parallel.make_move(&mut parallel_board, 10);
+
+            assert_eq!(single.score(), parallel.score(), "{}", moves);
+            assert_eq!(single_stats.depth_reached, parallel_stats.depth_reached);
+        }
+    }
+
+    #[test]
+    fn test_mirrored_positions() {
+        for moves in ["1", "12", "2131", "3344556", "6655"] {
+            let mut board = Board::from_moves(moves).unwrap();
+            let mut mirrored = board.mirror();
+            let mut alpha_beta = AlphaBeta::new();
+            alpha_beta.make_move(&mut board, 8);
+            let mut mirror_alpha_beta = AlphaBeta::new();
+            mirror_alpha_beta.make_move(&mut mirrored, 8);
+
+            assert_eq!(board.mirror().moves_played(), mirrored.moves_played(), "{}", moves);
+            assert_eq!(alpha_beta.score(), mirror_alpha_beta.score(), "{}", moves);
+        }
+
+        // a cold search of the mirror image would take as many nodes, but
+        // the table already has it
+        let board = Board::from_moves("2131").unwrap();
+        let mut alpha_beta = AlphaBeta::new();
+        let cold = alpha_beta.make_move(&mut board.clone(), 8);
+        let warm = alpha_beta.make_move(&mut board.mirror(), 8);
+        assert!(warm.nodes < cold.nodes, "{} {}", warm.nodes, cold.nodes);
+    }
+
+    #[test]
+    fn test_sample_move() {
+        rand::srand(5);
+
+        // the best move, the first one on ties
+        assert_eq!(sample_move(&[1, 3, -2, 3], 0.), 1);
+        assert_eq!(sample_move(&[], 0.), 0);
+
+        // scores that are all negative or sum to zero still give a move
+        for scores in [[-3, -1, -7], [0, 0, 0], [-2, 2, 0]] {
+            for _ in 0..20 {
+                assert!(sample_move(&scores, 1.) < 3);
+            }
+        }
+
+        // a cold temperature nearly always plays the best move, a hot one
+        // spreads its moves out
+        let count = |temperature: f32| (0..1000)
+            .filter(|_| sample_move(&[-4, 4, 0], temperature) == 1)
+            .count();
+        assert!(count(0.5) > 990);
+        let hot = count(10.);
+        assert!(hot > 333 && hot < 700, "{}", hot);
+    }
+
+    #[test]
+    fn test_small_table() {
+        // a tiny table loses most of what it learns, but not the answer
+        let mut small = AlphaBeta::new();
+        small.set_table_size(TableSize::Entries(1000));
+        let mut full = AlphaBeta::new();
+        for moves in ["4", "4453"] {
+            let mut small_board = Board::from_moves(moves).unwrap();
+            small.make_move(&mut small_board, 8);
+            let mut full_board = Board::from_moves(moves).unwrap();
+            full.make_move(&mut full_board, 8);
+            assert_eq!(small.score(), full.score(), "{}", moves);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_engine_save_and_load() {
+        let board = Board::from_moves("4453").unwrap();
+        let mut cold = AlphaBeta::new();
+        cold.set_limits(SearchLimits::depth(10));
+        cold.set_table_size(TableSize::Megabytes(1));
+        let col = cold.choose_move(&board);
+
+        let path = std::env::temp_dir().join(format!("connect_four_engine_{}.bin", std::process::id()));
+        Engine::save(&cold, &path).unwrap();
+        let mut warm = AlphaBeta::new();
+        warm.set_limits(SearchLimits::depth(10));
+        Engine::load(&mut warm, &path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(warm.choose_move(&board), col);
+        assert!(warm.nodes_explored() < cold.nodes_explored());
+    }
+
+    #[test]
+    fn test_plays_from_book() {
+        let board = Board::from_moves("4453635526322115177665").unwrap();
+        let book = OpeningBook::generate(&board, 2, &mut crate::ai::solver::Solver::new());
+        let col = book.best_move(&board).unwrap();
+
+        let mut alpha_beta = AlphaBeta::new();
+        alpha_beta.set_book(Arc::new(book));
+        assert_eq!(alpha_beta.choose_move(&board), col);
+        assert_eq!(alpha_beta.nodes_explored(), 0);
+    }
+}
@@ -0,0 +1,121 @@
+use crate::board::{Board, I_HEIGHT, I_WIDTH, U_WIDTH};
+use crate::player::Player;
+
+// Bottom cell of every column, shifted up to select a row
+const BOTTOM_ROW: u64 = ((1 << (U_WIDTH*U_WIDTH)) - 1) / 0x7F;
+
+// Rows 1, 3 and 5 counting from the bottom. White wants its threats on these
+// rows and Red on the others, since that is who gets to fill them when the
+// rest of the board runs out.
+const ODD_ROWS: u64 = BOTTOM_ROW | (BOTTOM_ROW << 2) | (BOTTOM_ROW << 4);
+const EVEN_ROWS: u64 = (BOTTOM_ROW << 1) | (BOTTOM_ROW << 3) | (BOTTOM_ROW << 5);
+const CENTRE_COLUMN: u64 = 0x3F << (3*U_WIDTH);
+
+// How much each feature is worth, in the same units as a search score. 
+// Weights of zero give the old behaviour of scoring the horizon as a draw.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weights {
+    pub open_three: i32, // per empty cell that completes a four
+    pub threat_parity: i32, // extra for those cells on the player's rows
+    pub centre: i32, // per disc in the centre column
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Weights { open_three: 1, threat_parity: 2, centre: 1 }
+    }
+}
+
+impl Weights {
+    pub fn zero() -> Self {
+        Weights { open_three: 0, threat_parity: 0, centre: 0 }
+    }
+}
+
+fn player_value(board: &Board, player: Player, weights: &Weights) -> i32 {
+    let threats = board.threats(player);
+    let good_rows = match player {
+        Player::White => ODD_ROWS,
+        Player::Red => EVEN_ROWS,
+    };
+
+    weights.open_three*threats.count_ones() as i32 
+        + weights.threat_parity*(threats & good_rows).count_ones() as i32 
+        + weights.centre*(board.player_bit_board(player) & CENTRE_COLUMN).count_ones() as i32
+}
+
+// Guess at the score of a position the search did not finish, for the side 
+// to move. It is kept smaller than any win or loss the search could have 
+// found before reaching this position.
+pub fn evaluate(board: &Board, weights: &Weights) -> i8 {
+    let player = board.side_to_move();
+    let value = player_value(board, player, weights) - player_value(board, player.opponent(), weights);
+    let bound = ((I_WIDTH*I_HEIGHT - 2 - board.counter)/2).max(0) as i32;
+
+    value.clamp(-bound, bound) as i8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(moves: &str) -> i8 {
+        evaluate(&Board::from_moves(moves).unwrap(), &Weights::default())
+    }
+
+    #[test]
+    fn test_symmetric_positions_are_even() {
+        assert_eq!(eval(""), 0);
+        assert_eq!(eval("17"), 0);
+        assert_eq!(eval("3535"), 0);
+    }
+
+    #[test]
+    fn test_prefers_centre() {
+        // red to move, so white's centre disc counts against it
+        assert!(eval("4") < 0);
+        assert!(eval("4") < eval("1"));
+        assert!(eval("41") > 0);
+    }
+
+    #[test]
+    fn test_prefers_open_three() {
+        // white has three along the bottom with red stacked on top
+        assert!(eval("1121311") < 0);
+        assert!(eval("11213117") > 0);
+
+        // red has the three this time, with white to move
+        assert!(eval("121374") < 0);
+    }
+
+    #[test]
+    fn test_threat_parity() {
+        // a white three stacked in the first column, with the cell above it
+        // on the fifth row, which is good for white, or on the fourth row,
+        // which is not. The other discs are spread out so neither side has
+        // anything else.
+        let odd = eval("7116171");
+        let even = eval("7617121");
+
+        assert!(odd < even);
+        assert!(odd < 0);
+    }
+
+    #[test]
+    fn test_weights() {
+        let board = Board::from_moves("1121311").unwrap();
+        assert_eq!(evaluate(&board, &Weights::zero()), 0);
+
+        let centre_only = Weights { open_three: 0, threat_parity: 0, centre: 1 };
+        assert_eq!(evaluate(&Board::from_moves("4").unwrap(), &centre_only), -1);
+    }
+
+    #[test]
+    fn test_stays_below_real_scores() {
+        // no matter the weights, the guess is smaller than a loss on the next move
+        let heavy = Weights { open_three: 100, threat_parity: 100, centre: 100 };
+        let board = Board::from_moves("1121311").unwrap();
+        let bound = (I_WIDTH*I_HEIGHT - board.counter)/2;
+        assert!(evaluate(&board, &heavy).abs() < bound);
+    }
+}
@@ -0,0 +1,385 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use quad_rand::RandomRange;
+
+use crate::ai::stats::SearchStats;
+use crate::board::{Board, Outcome, S_WIDTH, U_WIDTH};
+use crate::engine::Engine;
+use crate::player::Player;
+use crate::AIType;
+
+// How games are played out from a new node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rollout {
+    Random,
+    WinsAndBlocks, // take a win, else block the opponent's, else random
+}
+
+struct Node {
+    board: Board,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    untried: Vec<usize>, // columns not expanded yet
+    visits: u32,
+    wins: f32, // for the player who moved into this node, draws count half
+}
+
+impl Node {
+    fn new(board: Board, parent: Option<usize>) -> Self {
+        let untried = if board.outcome() == Outcome::InProgress {
+            (0..S_WIDTH).filter(|col| board.can_play(*col)).collect()
+        } else {
+            Vec::new()
+        };
+
+        Node { board, parent, children: Vec::new(), untried, visits: 0, wins: 0. }
+    }
+
+    fn mover(&self) -> Player {
+        self.board.side_to_move().opponent()
+    }
+}
+
+// Games played out per call to step(), few enough to fit in a frame
+const STEP_ITERATIONS: u32 = 250;
+
+fn column_of(bits: u64) -> usize {
+    bits.trailing_zeros() as usize / U_WIDTH as usize
+}
+
+// Monte Carlo tree search with UCT: grows a tree of the positions that look
+// best so far, scoring each new position by playing one game out from it.
+// It needs no evaluation function, but is weaker than AlphaBeta at the same
+// speed since it only samples the tree.
+pub struct Mcts {
+    iterations: u32,
+    exploration: f32,
+    rollout: Rollout,
+    stats: SearchStats,
+    principal_variation: Vec<u8>,
+    stop: Option<Arc<AtomicBool>>,
+    pending: Option<Vec<Node>> // tree being grown by step()
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Mcts {
+    pub fn new() -> Self {
+        Mcts {
+            iterations: 20_000,
+            exploration: std::f32::consts::SQRT_2,
+            rollout: Rollout::WinsAndBlocks,
+            stats: SearchStats::default(),
+            principal_variation: Vec::new(),
+            stop: None,
+            pending: None
+        }
+    }
+
+    // Plays more games out per move at harder levels
+    pub fn with_difficulty(ai_type: AIType) -> Self {
+        let mut mcts = Self::new();
+        mcts.set_iterations(match ai_type {
+            AIType::Beginner => 200,
+            AIType::Easy => 1_000,
+            AIType::Medium => 5_000,
+            AIType::Hard => 20_000,
+            AIType::Impossible => 50_000,
+        });
+        mcts
+    }
+
+    // Games played out per move
+    pub fn set_iterations(&mut self, iterations: u32) {
+        self.iterations = iterations.max(1);
+    }
+
+    // Higher values spend more time on moves that look worse so far
+    pub fn set_exploration(&mut self, exploration: f32) {
+        self.exploration = exploration;
+    }
+
+    pub fn set_rollout(&mut self, rollout: Rollout) {
+        self.rollout = rollout;
+    }
+
+    pub fn stats(&self) -> SearchStats {
+        self.stats
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    // Child with the best upper confidence bound
+    fn select(&self, tree: &[Node], node: usize) -> usize {
+        let log_visits = (tree[node].visits as f32).ln();
+        let uct = |child: &Node| {
+            child.wins / child.visits as f32 
+                + self.exploration * (log_visits / child.visits as f32).sqrt()
+        };
+
+        *tree[node].children.iter()
+            .max_by(|a, b| uct(&tree[**a]).total_cmp(&uct(&tree[**b])))
+            .unwrap()
+    }
+
+    fn rollout_move(&self, board: &Board) -> usize {
+        if self.rollout == Rollout::WinsAndBlocks {
+            let wins = board.winning_moves();
+            if wins != 0 {
+                return column_of(wins);
+            }
+
+            let blocks = board.threats(board.side_to_move().opponent()) & board.possible();
+            if blocks != 0 {
+                return column_of(blocks);
+            }
+        }
+
+        let columns: Vec<usize> = (0..S_WIDTH).filter(|col| board.can_play(*col)).collect();
+        columns[RandomRange::gen_range(0, columns.len())]
+    }
+
+    fn play_out(&self, board: &Board) -> Outcome {
+        let mut board = *board;
+        while board.outcome() == Outcome::InProgress {
+            let col = self.rollout_move(&board);
+            board.make_move(col);
+        }
+
+        board.outcome()
+    }
+
+    // A tree holding just the board, which grow() then builds on. The root is
+    // always index 0.
+    fn start_search(&mut self, board: &Board) -> Vec<Node> {
+        self.stats = SearchStats::default();
+        vec![Node::new(*board, None)]
+    }
+
+    // True once every game has been played out, or the search was stopped
+    fn is_done(&self) -> bool {
+        self.stopped() || self.stats.nodes >= self.iterations as u128
+    }
+
+    // Play up to count more games out, stopping early once the search is done
+    fn grow(&mut self, tree: &mut Vec<Node>, count: u32) {
+        for _ in 0..count {
+            if self.is_done() {
+                break;
+            }
+
+            // walk down through fully expanded nodes
+            let mut node = 0;
+            while tree[node].untried.is_empty() && !tree[node].children.is_empty() {
+                node = self.select(tree, node);
+            }
+
+            // add one new position
+            if !tree[node].untried.is_empty() {
+                let i = RandomRange::gen_range(0, tree[node].untried.len());
+                let col = tree[node].untried.swap_remove(i);
+                let mut next = tree[node].board;
+                next.make_move(col);
+
+                tree.push(Node::new(next, Some(node)));
+                let child = tree.len() - 1;
+                tree[node].children.push(child);
+                node = child;
+            }
+
+            // play it out and pass the result back up to the root
+            let outcome = self.play_out(&tree[node].board);
+            let mut current = Some(node);
+            while let Some(i) = current {
+                let n = &mut tree[i];
+                n.visits += 1;
+                n.wins += match outcome {
+                    Outcome::Won(player) if player == n.mover() => 1.,
+                    Outcome::Draw => 0.5,
+                    _ => 0.,
+                };
+                current = n.parent;
+            }
+
+            self.stats.nodes += 1;
+        }
+    }
+
+    // The most visited move at the root of a grown tree
+    fn finish_search(&mut self, tree: &[Node]) -> usize {
+        self.stats.depth_reached = self.record_line(tree) as u8;
+        match self.principal_variation.first() {
+            Some(col) => *col as usize,
+            // stopped before any game was played out
+            None => (0..S_WIDTH).find(|col| tree[0].board.can_play(*col)).unwrap()
+        }
+    }
+
+    // Don't leave a win to chance
+    fn immediate_win(&mut self, board: &Board) -> Option<usize> {
+        let wins = board.winning_moves();
+        if wins == 0 {
+            return None;
+        }
+
+        let col = column_of(wins);
+        self.stats = SearchStats::default();
+        self.principal_variation = vec![col as u8];
+        Some(col)
+    }
+
+    // Most visited line through the tree, returning its length
+    fn record_line(&mut self, tree: &[Node]) -> usize {
+        self.principal_variation.clear();
+        let mut node = 0;
+        while let Some(child) = tree[node].children.iter().max_by_key(|c| tree[**c].visits) {
+            node = *child;
+            self.principal_variation.push(*tree[node].board.moves_played().last().unwrap());
+        }
+
+        self.principal_variation.len()
+    }
+}
+
+impl Engine for Mcts {
+    fn choose_move(&mut self, board: &Board) -> usize {
+        if let Some(col) = self.immediate_win(board) {
+            return col;
+        }
+
+        let mut tree = self.start_search(board);
+        self.grow(&mut tree, self.iterations);
+        self.finish_search(&tree)
+    }
+
+    fn step(&mut self, board: &Board) -> Option<usize> {
+        if let Some(col) = self.immediate_win(board) {
+            self.pending = None;
+            return Some(col);
+        }
+
+        let mut tree = match self.pending.take() {
+            Some(tree) if tree[0].board.moves_played() == board.moves_played() => tree,
+            _ => self.start_search(board),
+        };
+
+        self.grow(&mut tree, STEP_ITERATIONS);
+        if self.is_done() {
+            Some(self.finish_search(&tree))
+        } else {
+            self.pending = Some(tree);
+            None
+        }
+    }
+
+    fn cancel(&mut self) {
+        self.pending = None;
+    }
+
+    fn set_stop_signal(&mut self, stop: Arc<AtomicBool>) {
+        self.stop = Some(stop);
+    }
+
+    fn principal_variation(&self) -> Vec<u8> {
+        self.principal_variation.clone()
+    }
+
+    fn stats(&self) -> Option<SearchStats> {
+        Some(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::random::Random;
+    use crate::engine::play;
+
+    #[test]
+    fn test_takes_win_and_blocks() {
+        quad_rand::srand(3);
+        let mut mcts = Mcts::new();
+        mcts.set_iterations(2_000);
+
+        // white to move with three stacked in the first column
+        assert_eq!(mcts.choose_move(&Board::from_moves("121212").unwrap()), 0);
+
+        // red has to stop white's three along the bottom
+        let col = mcts.choose_move(&Board::from_moves("1727").unwrap());
+        assert!(col == 0 || col == 2 || col == 3, "{}", col);
+        assert_eq!(mcts.choose_move(&Board::from_moves("17273").unwrap()), 3);
+        assert!(mcts.stats().nodes > 0);
+
+        // no search runs for a win, so none is reported
+        assert_eq!(mcts.choose_move(&Board::from_moves("121212").unwrap()), 0);
+        assert_eq!(mcts.stats().nodes, 0);
+    }
+
+    #[test]
+    fn test_rollout_policy() {
+        quad_rand::srand(5);
+        let mcts = Mcts::new();
+
+        // white takes the win
+        assert_eq!(mcts.rollout_move(&Board::from_moves("121314").unwrap()), 0);
+
+        // red blocks white's three
+        assert_eq!(mcts.rollout_move(&Board::from_moves("12131").unwrap()), 0);
+
+        // red takes its own win over blocking white's
+        assert_eq!(mcts.rollout_move(&Board::from_moves("1717172").unwrap()), 6);
+    }
+
+    #[test]
+    fn test_step() {
+        quad_rand::srand(11);
+        let mut mcts = Mcts::new();
+        mcts.set_iterations(1_000);
+
+        // the playouts are spread over several calls
+        let board = Board::from_moves("4453").unwrap();
+        let mut steps = 1;
+        let col = loop {
+            match mcts.step(&board) {
+                Some(col) => break col,
+                None => steps += 1
+            }
+        };
+        assert_eq!(steps, 4);
+        assert!(board.can_play(col));
+        assert_eq!(mcts.stats().nodes, 1_000);
+
+        // a win needs no search
+        assert_eq!(mcts.step(&Board::from_moves("121212").unwrap()), Some(0));
+
+        // cancelling starts over on the next step
+        assert_eq!(mcts.step(&board), None);
+        mcts.cancel();
+        assert_eq!(mcts.step(&board), None);
+        assert_eq!(mcts.stats().nodes, STEP_ITERATIONS as u128);
+    }
+
+    #[test]
+    fn test_headless_games() {
+        quad_rand::srand(7);
+        let mut mcts = Mcts::new();
+        mcts.set_iterations(1_000);
+        mcts.set_rollout(Rollout::Random);
+
+        let board = play(&mut mcts, &mut Random::new());
+        assert_eq!(board.outcome(), Outcome::Won(Player::White));
+        // the winning move was found without a search
+        assert_eq!(mcts.stats().nodes, 0);
+
+        mcts.set_rollout(Rollout::WinsAndBlocks);
+        let board = play(&mut Random::new(), &mut mcts);
+        assert_eq!(board.outcome(), Outcome::Won(Player::Red));
+    }
+}
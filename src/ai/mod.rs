@@ -1,2 +1,30 @@
 pub mod alpha_beta;
-pub mod random;
\ No newline at end of file
+pub mod evaluation;
+pub mod mcts;
+pub mod random;
+pub mod solver;
+pub mod stats;
+
+use std::sync::Arc;
+
+use crate::book::OpeningBook;
+use crate::engine::Engine;
+use crate::{AIType, EngineKind};
+
+// Build the opponent for a difficulty level, searching with the given number
+// of threads. Only the strongest alpha-beta levels play from the opening 
+// book, since it only knows perfect moves.
+pub fn new_engine(kind: EngineKind, ai_type: AIType, book: Option<&Arc<OpeningBook>>, threads: usize) -> Box<dyn Engine> {
+    match (kind, ai_type) {
+        (EngineKind::MonteCarlo, _) => Box::new(mcts::Mcts::with_difficulty(ai_type)),
+        (EngineKind::AlphaBeta, AIType::Beginner) => Box::new(random::Random::new()),
+        (EngineKind::AlphaBeta, _) => {
+            let mut engine = alpha_beta::AlphaBeta::with_difficulty(ai_type);
+            engine.set_threads(threads);
+            if let Some(book) = book.filter(|_| matches!(ai_type, AIType::Hard | AIType::Impossible)) {
+                engine.set_book(book.clone());
+            }
+            Box::new(engine)
+        }
+    }
+}
@@ -1,11 +1,21 @@
-use std::mem::swap;
-use macroquad::rand::RandomRange;
+use quad_rand::RandomRange;
 
-use crate::board::Board;
+use crate::board::{Board, S_WIDTH};
+use crate::engine::Engine;
 
-pub fn make_move(board: &mut Board) {
-    let mut boards = board.get_next_boards();
-    let i = RandomRange::gen_range(0, boards.len());
+// Plays any legal column with equal probability
+#[derive(Default)]
+pub struct Random;
 
-    swap(board, &mut boards[i]);
-}
\ No newline at end of file
+impl Random {
+    pub fn new() -> Self {
+        Random
+    }
+}
+
+impl Engine for Random {
+    fn choose_move(&mut self, board: &Board) -> usize {
+        let columns: Vec<usize> = (0..S_WIDTH).filter(|col| board.can_play(*col)).collect();
+        columns[RandomRange::gen_range(0, columns.len())]
+    }
+}
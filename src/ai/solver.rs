@@ -0,0 +1,370 @@
+use std::io;
+use std::path::Path;
+
+use crate::board::{Board, Outcome, I_HEIGHT, I_WIDTH, S_WIDTH};
+use crate::engine::Engine;
+use crate::transition_table::TableSize;
+use crate::ai::alpha_beta::{AlphaBeta, MoveOrdering};
+
+// Game theoretic value of a position for the side to move. Positive scores 
+// are wins, negative scores are losses, and 0 is a draw. The sooner the win 
+// the bigger the score: a player winning with their n-th disc scores 22 - n.
+pub type Score = i8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WeakScore {
+    Win,
+    Draw,
+    Loss,
+}
+
+// How solve() finds the exact score
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchWindow {
+    Full, // one search between the lowest and highest possible scores
+    Null, // a binary search on the score made of zero-width searches
+}
+
+// Perfect play solver built on the alpha-beta negamax. Searching to the end of 
+// the game means every node is searched to a depth of 42 - counter, so exact 
+// results are shared between calls through the transposition table.
+pub struct Solver {
+    alpha_beta: AlphaBeta,
+    window: SearchWindow
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Solver {
+    pub fn new() -> Self {
+        Solver { alpha_beta: AlphaBeta::new(), window: SearchWindow::Null }
+    }
+
+    pub fn set_window(&mut self, window: SearchWindow) {
+        self.window = window;
+    }
+
+    // Exact score of the position
+    pub fn solve(&mut self, board: &Board) -> Score {
+        if let Some(score) = Self::immediate_score(board) {
+            return score;
+        }
+
+        let mut min = -(I_WIDTH*I_HEIGHT - board.counter)/2;
+        let mut max = (I_WIDTH*I_HEIGHT + 1 - board.counter)/2;
+        if self.window == SearchWindow::Full {
+            return self.search(board, min, max);
+        }
+
+        // Each search only tells whether the score is above or below a guess,
+        // but prunes far more than a full window. Guesses lean towards 0 
+        // since most positions are close to a draw.
+        while min < max {
+            let mut guess = min + (max - min)/2;
+            if guess <= 0 && min/2 < guess {
+                guess = min/2;
+            } else if guess >= 0 && max/2 > guess {
+                guess = max/2;
+            }
+
+            let score = self.search(board, guess, guess + 1);
+            if score <= guess {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+
+        min
+    }
+
+    // Only whether the side to move wins, draws, or loses, which is much 
+    // cheaper to find than the exact score
+    pub fn solve_weak(&mut self, board: &Board) -> WeakScore {
+        match self.search(board, -1, 1) {
+            s if s > 0 => WeakScore::Win,
+            s if s < 0 => WeakScore::Loss,
+            _ => WeakScore::Draw,
+        }
+    }
+
+    // Score of playing each column, None for full columns
+    pub fn column_scores(&mut self, board: &Board) -> [Option<Score>; S_WIDTH] {
+        let mut scores = [None; S_WIDTH];
+        if board.outcome() != Outcome::InProgress {
+            return scores;
+        }
+
+        for (col, score) in scores.iter_mut().enumerate() {
+            let mut next = *board;
+            if next.make_move(col) {
+                *score = Some(-self.solve(&next));
+            }
+        }
+
+        scores
+    }
+
+    // Number of moves, counting both players, until the winning disc is 
+    // played. None if the score is a draw.
+    pub fn moves_to_win(board: &Board, score: Score) -> Option<u8> {
+        if score == 0 {
+            return None;
+        }
+
+        // the winner's n-th disc is the 2(n-1)-th move of the game for white 
+        // and the 2(n-1)+1-th for red
+        let winner_is_side_to_move = score > 0;
+        let winner_is_white = board.is_white_turn() == winner_is_side_to_move;
+        let disc = 22 - score.abs();
+        let move_index = 2*(disc - 1) + if winner_is_white { 0 } else { 1 };
+        Some((move_index - board.counter + 1) as u8)
+    }
+
+    // Solved positions can be saved and loaded again, so later sessions 
+    // start warm
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.alpha_beta.save_table(path)
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.alpha_beta.load_table(path)
+    }
+
+    pub fn set_table_size(&mut self, size: TableSize) {
+        self.alpha_beta.set_table_size(size);
+    }
+
+    pub fn set_move_ordering(&mut self, ordering: MoveOrdering) {
+        self.alpha_beta.set_move_ordering(ordering);
+    }
+
+    // Native builds can solve with several threads sharing the table
+    pub fn set_threads(&mut self, threads: usize) {
+        self.alpha_beta.set_threads(threads);
+    }
+
+    // Number of positions visited by the solver so far
+    pub fn nodes_explored(&self) -> u128 {
+        self.alpha_beta.nodes_explored()
+    }
+
+    // Score of a position that needs no search
+    fn immediate_score(board: &Board) -> Option<Score> {
+        match board.outcome() {
+            Outcome::Draw => Some(0),
+            // the player who just moved won on move number counter - 1
+            Outcome::Won(_) => Some(-(I_WIDTH*I_HEIGHT + 2 - board.counter)/2),
+            // negamax expects the side to move to have no winning move
+            Outcome::InProgress if board.winning_moves() != 0 => 
+                Some((I_WIDTH*I_HEIGHT + 1 - board.counter)/2),
+            Outcome::InProgress => None,
+        }
+    }
+
+    fn search(&mut self, board: &Board, alpha: i8, beta: i8) -> Score {
+        match Self::immediate_score(board) {
+            Some(score) => score,
+            None => self.alpha_beta.solve(board, alpha, beta),
+        }
+    }
+}
+
+// Perfect opponent, taking the quickest win or the slowest loss and the 
+// most central column on ties. Slow early in the game without a book.
+impl Engine for Solver {
+    fn choose_move(&mut self, board: &Board) -> usize {
+        let scores = self.column_scores(board);
+        board.column_order().into_iter()
+            .rev()
+            .filter(|col| scores[*col].is_some())
+            .max_by_key(|col| scores[*col])
+            .expect("no legal move")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::random_opening;
+
+    // Plain minimax without any pruning to check the solver against
+    fn reference(board: &Board) -> Score {
+        if board.winning_moves() != 0 {
+            return (I_WIDTH*I_HEIGHT + 1 - board.counter)/2;
+        }
+        if board.is_draw() {
+            return 0;
+        }
+
+        let mut best = -I_WIDTH*I_HEIGHT;
+        for next in board.get_next_boards() {
+            best = best.max(-reference(&next));
+        }
+
+        best
+    }
+
+    // The same random positions every time
+    fn random_positions(count: usize, moves: i8) -> Vec<Board> {
+        quad_rand::srand(3);
+        (0..count).map(|_| random_opening(moves)).collect()
+    }
+
+    #[test]
+    fn test_immediate_results() {
+        let mut solver = Solver::new();
+
+        // white to move wins with their 4th disc
+        let board = Board::from_moves("121212").unwrap();
+        assert_eq!(solver.solve(&board), 18);
+        assert_eq!(Solver::moves_to_win(&board, 18), Some(1));
+        assert_eq!(solver.solve_weak(&board), WeakScore::Win);
+
+        // white has already won with their 4th disc
+        let board = Board::from_moves("1212121").unwrap();
+        assert_eq!(solver.solve(&board), -18);
+        assert_eq!(solver.column_scores(&board), [None; S_WIDTH]);
+
+        // red can not stop both threats on the bottom row
+        let board = Board::from_moves("44553").unwrap();
+        assert_eq!(solver.solve(&board), -(I_WIDTH*I_HEIGHT - 6)/2);
+        assert_eq!(solver.solve_weak(&board), WeakScore::Loss);
+        assert_eq!(Solver::moves_to_win(&board, -(I_WIDTH*I_HEIGHT - 6)/2), Some(2));
+
+        let drawn = Board::from_moves("451467634277672635122257755345341336642111").unwrap();
+        assert_eq!(solver.solve(&drawn), 0);
+        assert_eq!(solver.solve_weak(&drawn), WeakScore::Draw);
+        assert_eq!(Solver::moves_to_win(&drawn, 0), None);
+    }
+
+    #[test]
+    fn test_null_window_matches_full_window() {
+        let mut full = Solver::new();
+        full.set_window(SearchWindow::Full);
+        let mut null = Solver::new();
+        null.set_window(SearchWindow::Null);
+
+        for board in random_positions(20, 24) {
+            assert_eq!(null.solve(&board), full.solve(&board), "{}", board.to_move_string());
+        }
+
+        let drawn = Board::from_moves("451467634277672635122257755345341336642111").unwrap();
+        assert_eq!(null.solve(&drawn), 0);
+        let board = Board::from_moves("44553").unwrap();
+        assert_eq!(null.solve(&board), -(I_WIDTH*I_HEIGHT - 6)/2);
+    }
+
+    #[test]
+    fn test_mirrored_positions() {
+        let mut solver = Solver::new();
+        for board in random_positions(20, 24) {
+            let mirrored = board.mirror();
+            assert_eq!(solver.solve(&board), solver.solve(&mirrored));
+
+            let mut scores = solver.column_scores(&board);
+            scores.reverse();
+            assert_eq!(scores, solver.column_scores(&mirrored), "{}", board.to_move_string());
+
+            // a symmetric position is its own mirror
+            if board.hash() != mirrored.hash() {
+                assert_eq!(solver.choose_move(&board), S_WIDTH - 1 - solver.choose_move(&mirrored));
+            }
+        }
+    }
+
+    #[test]
+    fn test_symmetric_positions_share_entries() {
+        // the two sides of a symmetric position are mirrors of each other, so
+        // only one of them has to be searched. Keyed by hash() alone these
+        // took 957,702 nodes.
+        let mut solver = Solver::new();
+        for moves in ["1177335533551177", "2266442266442266", "44117711774444",
+                      "2266226644442266", "1177226611771177", "2266117711772266"] {
+            solver.solve(&Board::from_moves(moves).unwrap());
+        }
+
+        assert!(solver.nodes_explored() < 600_000, "{}", solver.nodes_explored());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let positions = random_positions(5, 26);
+        let mut cold = Solver::new();
+        cold.set_table_size(TableSize::Megabytes(1));
+        let scores: Vec<Score> = positions.iter().map(|board| cold.solve(board)).collect();
+
+        let path = std::env::temp_dir().join(format!("connect_four_solver_{}.bin", std::process::id()));
+        cold.save(&path).unwrap();
+        let mut warm = Solver::new();
+        warm.load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // the saved results are found straight away
+        let warm_scores: Vec<Score> = positions.iter().map(|board| warm.solve(board)).collect();
+        assert_eq!(warm_scores, scores);
+        assert!(warm.nodes_explored() < cold.nodes_explored() / 10);
+    }
+
+    #[test]
+    fn test_move_ordering() {
+        let positions = random_positions(10, 26);
+        let mut nodes = Vec::new();
+        let mut results = Vec::new();
+        let all = MoveOrdering { threats: true, killers: true, history: true };
+        for ordering in [MoveOrdering::none(), MoveOrdering::default(), all] {
+            let mut solver = Solver::new();
+            solver.set_move_ordering(ordering);
+            results.push(positions.iter().map(|board| solver.solve(board)).collect::<Vec<_>>());
+            nodes.push(solver.nodes_explored());
+        }
+
+        // the order changes how much gets searched, never the answer
+        assert_eq!(results[0], results[1]);
+        assert_eq!(results[0], results[2]);
+        assert!(nodes[1] < nodes[0], "{:?}", nodes);
+    }
+
+    #[test]
+    fn test_threads_match_reference() {
+        let mut solver = Solver::new();
+        solver.set_threads(4);
+        for board in random_positions(5, 30) {
+            assert_eq!(solver.solve(&board), reference(&board), "{}", board.to_move_string());
+        }
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        let mut solver = Solver::new();
+        for board in random_positions(20, 32) {
+            let expected = reference(&board);
+            assert_eq!(solver.solve(&board), expected, "{}", board.to_move_string());
+
+            let weak = solver.solve_weak(&board);
+            assert_eq!(weak == WeakScore::Win, expected > 0);
+            assert_eq!(weak == WeakScore::Loss, expected < 0);
+
+            let scores = solver.column_scores(&board);
+            assert_eq!(scores.iter().flatten().max(), Some(&expected));
+            assert_eq!(scores[solver.choose_move(&board)], Some(expected));
+            for (col, score) in scores.iter().enumerate() {
+                let mut next = board;
+                if next.make_move(col) {
+                    let s = if matches!(next.outcome(), Outcome::Won(_)) {
+                        (I_WIDTH*I_HEIGHT + 1 - board.counter)/2
+                    } else {
+                        -reference(&next)
+                    };
+                    assert_eq!(*score, Some(s));
+                } else {
+                    assert_eq!(*score, None);
+                }
+            }
+        }
+    }
+}
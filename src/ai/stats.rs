@@ -0,0 +1,79 @@
+use std::fmt;
+use std::time::Duration;
+
+// Counters from one search, for tuning and the debug overlay
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SearchStats {
+    pub nodes: u128,
+    pub elapsed: Duration, // zero on the web, which has no clock
+    pub tt_probes: u64,
+    pub tt_hits: u64,
+    pub tt_collisions: u64, // probes that found a different position in the slot
+    pub cutoffs: u64,
+    pub depth_reached: u8,
+}
+
+impl SearchStats {
+    pub fn nodes_per_second(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0. { self.nodes as f64 / secs } else { 0. }
+    }
+
+    pub fn tt_hit_rate(&self) -> f64 {
+        if self.tt_probes > 0 { self.tt_hits as f64 / self.tt_probes as f64 } else { 0. }
+    }
+
+    // Branching factor that would visit the same number of nodes in a 
+    // uniform tree as deep as the search went
+    pub fn branching_factor(&self) -> f64 {
+        if self.depth_reached > 0 {
+            (self.nodes as f64).powf(1. / self.depth_reached as f64)
+        } else {
+            0.
+        }
+    }
+}
+
+// One row of a markdown table: | depth | nodes | elapsed | knodes/s | hit rate | cutoffs |
+impl fmt::Display for SearchStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f, 
+            "| {} | {} | {:?} | {:.0} | {:.1}% | {} |", 
+            self.depth_reached, 
+            self.nodes, 
+            self.elapsed, 
+            self.nodes_per_second() / 1000., 
+            100. * self.tt_hit_rate(), 
+            self.cutoffs
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_derived_values() {
+        let stats = SearchStats {
+            nodes: 1000,
+            elapsed: Duration::from_millis(500),
+            tt_probes: 200,
+            tt_hits: 50,
+            tt_collisions: 10,
+            cutoffs: 30,
+            depth_reached: 3,
+        };
+
+        assert_eq!(stats.nodes_per_second(), 2000.);
+        assert_eq!(stats.tt_hit_rate(), 0.25);
+        assert!((stats.branching_factor() - 10.).abs() < 1e-9);
+        assert_eq!(stats.to_string(), "| 3 | 1000 | 500ms | 2 | 25.0% | 30 |");
+
+        let empty = SearchStats::default();
+        assert_eq!(empty.nodes_per_second(), 0.);
+        assert_eq!(empty.tt_hit_rate(), 0.);
+        assert_eq!(empty.branching_factor(), 0.);
+    }
+}
@@ -0,0 +1,42 @@
+// Writes an opening book for the AI to play from.
+//
+//   cargo run --release --no-default-features --bin build-book -- <plies> <file> [moves]
+//
+// Every position up to <plies> moves past the starting position (the empty 
+// board, or the given moves) gets solved, so this takes a while for more 
+// than a few plies.
+
+use std::env;
+use std::process;
+use std::time::Instant;
+
+use connect_four::ai::solver::Solver;
+use connect_four::board::Board;
+use connect_four::book::OpeningBook;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("usage: {} <plies> <file> [moves]", args[0]);
+        process::exit(1);
+    }
+
+    let plies: u8 = args[1].parse().unwrap_or_else(|_| {
+        eprintln!("plies must be a number, found '{}'", args[1]);
+        process::exit(1);
+    });
+
+    let root = Board::from_moves(args.get(3).map_or("", String::as_str)).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        process::exit(1);
+    });
+
+    let start = Instant::now();
+    let book = OpeningBook::generate(&root, plies, &mut Solver::new());
+    if let Err(err) = book.save(&args[2]) {
+        eprintln!("could not write {}: {}", args[2], err);
+        process::exit(1);
+    }
+
+    println!("{} positions in {:.1?}", book.len(), start.elapsed());
+}
@@ -1,4 +1,7 @@
+use std::fmt;
+
 use crate::cell::Cell;
+use crate::player::Player;
 
 pub const S_WIDTH: usize = 7;
 pub const S_HEIGHT: usize = 6;
@@ -16,6 +19,9 @@ pub const COLUMN_ORDER: [usize; 7] = [3,4,2,5,1,6,0]; // search from the middle
 
 pub const MIN_SCORE: i8 = -(I_WIDTH*I_HEIGHT)/2 + 3;
 
+// Every playable cell, which leaves out the additional row on top of each column
+pub const BOARD_MASK: u64 = ((1 << (U_WIDTH*U_WIDTH)) - 1) / 0x7F * 0x3F;
+
 /* 
 https://github.com/denkspuren/BitboardC4/blob/master/BitboardDesign.md
   6 13 20 27 34 41 48   55 62     Additional row
@@ -31,18 +37,69 @@ https://github.com/denkspuren/BitboardC4/blob/master/BitboardDesign.md
 Using above as basis for this implementation.
 */
 
+// Errors when reading a position from a column-sequence string. Index is the
+// 0-based position of the offending character in the string.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MoveError {
+    InvalidColumn { index: usize, found: char },
+    ColumnFull { index: usize, col: usize },
+    GameOver { index: usize },
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveError::InvalidColumn { index, found } => 
+                write!(f, "invalid column '{}' at move {}, expected 1-{}", found, index + 1, S_WIDTH),
+            MoveError::ColumnFull { index, col } => 
+                write!(f, "column {} is full at move {}", col + 1, index + 1),
+            MoveError::GameOver { index } => 
+                write!(f, "move {} played after the game was over", index + 1),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    InProgress,
+    Won(Player),
+    Draw,
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct Board {
-    pub bit_board: [u64; 2], // 0 is player and 1 is the AI
+    pub bit_board: [u64; 2], // indexed by Player::index()
     height: [u8; 7],
+    moves: [u8; S_WIDTH*S_HEIGHT], // columns played, in order, up to counter
     pub counter: i8
 }
 
+impl Default for Board {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Reverse the order of the columns of a key. Each column of hash() only
+// depends on that column, so this is the key of the mirrored position.
+fn mirror_key(key: u64) -> u64 {
+    let mut mirrored = 0;
+    for col in 0..S_WIDTH {
+        let column = (key >> (col * U_WIDTH as usize)) & 0x7F;
+        mirrored |= column << ((S_WIDTH - 1 - col) * U_WIDTH as usize);
+    }
+
+    mirrored
+}
+
 impl Board {
     pub fn new() -> Board {
         Board { 
             bit_board: [0; 2],
             height: [0, 7, 14, 21, 28, 35, 42],
+            moves: [0; S_WIDTH*S_HEIGHT],
             counter: 0,
         }
     }
@@ -51,6 +108,7 @@ impl Board {
         self.bit_board[0] = 0;
         self.bit_board[1] = 0;
         self.height = [0, 7, 14, 21, 28, 35, 42];
+        self.moves = [0; S_WIDTH*S_HEIGHT];
         self.counter = 0;
     }
 
@@ -63,7 +121,7 @@ impl Board {
         let mut boards = [None; S_WIDTH];
         let mut i = 0;
         
-        for col in COLUMN_ORDER {
+        for col in self.column_order() {
             let mut new_board = *self;
             let new_board_is_valid = new_board.make_move(col);
             if new_board_is_valid && new_board.winning_moves() == 0 {
@@ -75,10 +133,49 @@ impl Board {
         boards
     }
 
+    // Columns of the boards get_next_non_losing_boards() would return, for 
+    // searches that make and undo moves on one board instead of copying it.
+    // They come in plain COLUMN_ORDER, since mirroring the ties at every node
+    // of a search costs more nodes than it saves.
+    // Based on possibleNonLosingMoves() in:
+    // https://github.com/PascalPons/connect4/blob/7ed79f6e6315c0f95ee35194520dd615eddbd27d/position.hpp
+    pub fn non_losing_columns(&self) -> [Option<usize>; S_WIDTH] {
+        let mut columns = [None; S_WIDTH];
+        let opponent_wins = self.threats(self.side_to_move().opponent());
+        let mut moves = self.possible();
+
+        // a threat the opponent can play next has to be blocked, two can't be
+        let forced = moves & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                return columns;
+            }
+            moves = forced;
+        }
+
+        // and don't play underneath one
+        moves &= !(opponent_wins >> 1);
+
+        let mut i = 0;
+        for col in COLUMN_ORDER {
+            if moves & (0x7F << (col * U_WIDTH as usize)) != 0 {
+                columns[i] = Some(col);
+                i += 1;
+            }
+        }
+
+        columns
+    }
+
+    // Bit of the cell the next disc played in the column lands in
+    pub fn next_cell(&self, col: usize) -> usize {
+        self.height[col] as usize
+    }
+
     // Return all possible next boards
     pub fn get_next_boards(&self) -> Vec<Board> {
         let mut boards: Vec<Board> = Vec::new();
-        for column in COLUMN_ORDER {
+        for column in self.column_order() {
             let mut new_board = *self;
             if new_board.make_move(column) {
                 boards.push(new_board);
@@ -88,10 +185,34 @@ impl Board {
         boards
     }
 
+    pub fn can_play(&self, col: usize) -> bool {
+        col < S_WIDTH && self.height[col] < U_HEIGHT + (col as u8) * U_WIDTH
+    }
+
     pub fn is_white_turn(&self) -> bool{
         self.counter % 2 == 0
     }
 
+    pub fn side_to_move(&self) -> Player {
+        if self.is_white_turn() { Player::White } else { Player::Red }
+    }
+
+    pub fn player_bit_board(&self, player: Player) -> u64 {
+        self.bit_board[player.index()]
+    }
+
+    pub fn outcome(&self) -> Outcome {
+        // only the player who moved last can have just won
+        let last = self.side_to_move().opponent();
+        if self.is_game_over(last) {
+            Outcome::Won(last)
+        } else if self.is_draw() {
+            Outcome::Draw
+        } else {
+            Outcome::InProgress
+        }
+    }
+
     pub fn make_move(&mut self, col: usize) -> bool {
         let h = self.height[col];
         if h >= U_HEIGHT + (col as u8) * U_WIDTH {
@@ -101,13 +222,79 @@ impl Board {
         let move_pos = (1_u64) << h;
         self.bit_board[(self.counter & 1) as usize] ^= move_pos; 
         self.height[col] += 1;
+        self.moves[self.counter as usize] = col as u8;
         self.counter += 1;
         
         true
     }
 
+    // Take back the last move played. Returns false if there is nothing to
+    // undo.
+    pub fn undo_move(&mut self) -> bool {
+        if self.counter == 0 {
+            return false;
+        }
+
+        self.counter -= 1;
+        let col = self.moves[self.counter as usize] as usize;
+        self.moves[self.counter as usize] = 0;
+        self.height[col] -= 1;
+
+        let move_pos = (1_u64) << self.height[col];
+        self.bit_board[(self.counter & 1) as usize] ^= move_pos;
+
+        true
+    }
+
+    // Build a board from a sequence of 1-based columns, e.g. "4453", which is
+    // the notation used by public Connect-Four solvers and benchmark files.
+    pub fn from_moves(moves: &str) -> Result<Board, MoveError> {
+        let mut board = Board::new();
+        for (index, found) in moves.chars().enumerate() {
+            let col = match found.to_digit(10) {
+                Some(c) if c >= 1 && c as usize <= S_WIDTH => (c - 1) as usize,
+                _ => return Err(MoveError::InvalidColumn { index, found }),
+            };
+
+            if board.outcome() != Outcome::InProgress {
+                return Err(MoveError::GameOver { index });
+            }
+
+            if !board.make_move(col) {
+                return Err(MoveError::ColumnFull { index, col });
+            }
+        }
+
+        Ok(board)
+    }
+
+    // The same position reflected left to right, with the move history 
+    // reflected as well
+    pub fn mirror(&self) -> Board {
+        let mut board = Board::new();
+        for col in self.moves_played() {
+            board.make_move(S_WIDTH - 1 - *col as usize);
+        }
+
+        board
+    }
+
+    // Inverse of from_moves
+    pub fn to_move_string(&self) -> String {
+        self.moves_played()
+            .iter()
+            .map(|col| char::from(b'1' + col))
+            .collect()
+    }
+
+    // Columns played so far, oldest first
+    pub fn moves_played(&self) -> &[u8] {
+        &self.moves[..self.counter as usize]
+    }
+
     // https://github.com/denkspuren/BitboardC4/blob/master/BitboardDesign.md#are-there-four-in-a-row
-    pub fn is_game_over(&self, bit_board: u64) -> bool {
+    pub fn is_game_over(&self, player: Player) -> bool {
+        let bit_board = self.player_bit_board(player);
         let diag_1 = bit_board & (bit_board >> 6); // diagonal \
         let diag_2 = bit_board & (bit_board >> 8);
         let horizontal = bit_board & (bit_board >> 7);
@@ -119,6 +306,47 @@ impl Board {
         vertical & (vertical >> 2) != 0
     }
 
+    // Mask of every cell that is part of a line of four for the given player
+    pub fn winning_mask(&self, player: Player) -> u64 {
+        let bit_board = self.player_bit_board(player);
+        let mut mask = 0;
+        for shift in [1, 6, 7, 8] { // vertical, diagonal \, horizontal, diagonal /
+            let starts = bit_board 
+                & (bit_board >> shift) 
+                & (bit_board >> (2*shift)) 
+                & (bit_board >> (3*shift));
+            mask |= starts | (starts << shift) | (starts << (2*shift)) | (starts << (3*shift));
+        }
+
+        mask
+    }
+
+    // Every line of four for the given player as (column, row) pairs, where
+    // row 0 is the bottom of the board.
+    pub fn winning_lines(&self, player: Player) -> Vec<[(usize, usize); 4]> {
+        let bit_board = self.player_bit_board(player);
+        let mut lines = Vec::new();
+        for shift in [1, 6, 7, 8] {
+            let mut starts = bit_board 
+                & (bit_board >> shift) 
+                & (bit_board >> (2*shift)) 
+                & (bit_board >> (3*shift));
+
+            while starts != 0 {
+                let start = starts.trailing_zeros() as usize;
+                let mut line = [(0, 0); 4];
+                for (i, cell) in line.iter_mut().enumerate() {
+                    let index = start + i*shift;
+                    *cell = (index / U_WIDTH as usize, index % U_WIDTH as usize);
+                }
+                lines.push(line);
+                starts &= starts - 1;
+            }
+        }
+
+        lines
+    }
+
     pub fn is_draw(&self) -> bool {
         self.counter == 42 // U_WIDTH * U_HEIGHT = 6 * 7 = 42
     }
@@ -129,6 +357,42 @@ impl Board {
         self.bit_board[index] + (self.bit_board[index] | self.bit_board[index_2])
     }
 
+    // hash() of the mirrored position
+    pub fn mirror_key(&self) -> u64 {
+        mirror_key(self.hash())
+    }
+
+    // The same key for a position and its mirror image, so lookups can share
+    // results between them
+    pub fn canonical_key(&self) -> u64 {
+        self.canonical().0
+    }
+
+    // True if canonical_key() is the key of this side of the board rather 
+    // than of its mirror
+    pub fn is_canonical(&self) -> bool {
+        self.canonical().1
+    }
+
+    // canonical_key() and is_canonical() together, for searches that need 
+    // both at every node
+    pub fn canonical(&self) -> (u64, bool) {
+        let key = self.hash();
+        let mirrored = mirror_key(key);
+        (key.min(mirrored), key <= mirrored)
+    }
+
+    // COLUMN_ORDER as seen from the canonical side of the board, so that a 
+    // position and its mirror try their moves in mirrored order and break 
+    // ties the same way
+    pub fn column_order(&self) -> [usize; S_WIDTH] {
+        if self.is_canonical() {
+            COLUMN_ORDER
+        } else {
+            COLUMN_ORDER.map(|col| S_WIDTH - 1 - col)
+        }
+    }
+
     #[allow(dead_code)]
     fn moves(&self) -> u64 {
         let mut mask: u64 = 0;
@@ -162,6 +426,17 @@ impl Board {
         
         moves & self.possible()
     }
+
+    // Empty cells that would complete four in a row for the player, whether
+    // or not they can be played yet
+    pub fn threats(&self, player: Player) -> u64 {
+        let moves = self.compute_winning_positions(
+            self.bit_board[player.index()], 
+            self.bit_board[player.opponent().index()]
+        );
+
+        moves & BOARD_MASK
+    }
     
     /* 
     https://github.com/denkspuren/BitboardC4/blob/master/BitboardDesign.md
@@ -184,25 +459,25 @@ impl Board {
 
         //horizontal
         let mut p = (bit_board << U_WIDTH) & (bit_board << (2*U_WIDTH));
-        r |= p & (bit_board << 3*(U_WIDTH));
+        r |= p & (bit_board << (3*(U_WIDTH)));
         r |= p & (bit_board >> U_WIDTH);
         p = (bit_board >> U_WIDTH) & (bit_board >> (2*U_WIDTH));
         r |= p & (bit_board << U_WIDTH);
-        r |= p & (bit_board >> 3*U_WIDTH);
+        r |= p & (bit_board >> (3*U_WIDTH));
 
         //diagonal 1 \
-        p = (bit_board << (U_WIDTH-1)) & (bit_board << 2*(U_WIDTH-1));
-        r |= p & (bit_board << 3*(U_WIDTH-1));
+        p = (bit_board << (U_WIDTH-1)) & (bit_board << (2*(U_WIDTH-1)));
+        r |= p & (bit_board << (3*(U_WIDTH-1)));
         r |= p & (bit_board >> (U_WIDTH-1));
-        p = (bit_board >> (U_WIDTH-1)) & (bit_board >> 2*(U_WIDTH-1));
+        p = (bit_board >> (U_WIDTH-1)) & (bit_board >> (2*(U_WIDTH-1)));
         r |= p & (bit_board << (U_WIDTH-1));
         r |= p & (bit_board >> (3*(U_WIDTH-1)));
 
         //diagonal 2 /
-        p = (bit_board << (U_WIDTH+1)) & (bit_board << 2*(U_WIDTH+1));
-        r |= p & (bit_board << 3*(U_WIDTH+1));
+        p = (bit_board << (U_WIDTH+1)) & (bit_board << (2*(U_WIDTH+1)));
+        r |= p & (bit_board << (3*(U_WIDTH+1)));
         r |= p & (bit_board >> (U_WIDTH+1));
-        p = (bit_board >> (U_WIDTH+1)) & (bit_board >> 2*(U_WIDTH+1));
+        p = (bit_board >> (U_WIDTH+1)) & (bit_board >> (2*(U_WIDTH+1)));
         r |= p & (bit_board << (U_WIDTH+1));
         r |= p & (bit_board >> (3*(U_WIDTH+1)));
 
@@ -212,13 +487,24 @@ impl Board {
     // refer to board above for the for magic numbers to make sense
     pub fn get_cells(&self) -> [Cell; S_WIDTH*S_HEIGHT] {
         let mut board = [Cell::Empty; S_WIDTH*S_HEIGHT];
+        let victory = [
+            self.winning_mask(Player::White),
+            self.winning_mask(Player::Red)
+        ];
+        let white = self.player_bit_board(Player::White);
+        let red = self.player_bit_board(Player::Red);
+
         let mut i = 0;
         for row in (0..6).rev() {
             for col in 0..S_WIDTH {
                 let index = row + col*S_WIDTH;
-                if self.bit_board[0] & (1 << index) != 0 {
+                if victory[0] & (1 << index) != 0 {
+                    board[i] = Cell::WhiteVictory;
+                } else if victory[1] & (1 << index) != 0 {
+                    board[i] = Cell::RedVictory;
+                } else if white & (1 << index) != 0 {
                     board[i] = Cell::White;
-                } else if self.bit_board[1] & (1 << index) != 0 {
+                } else if red & (1 << index) != 0 {
                     board[i] = Cell::Red;
                 } 
                 i += 1;
@@ -239,7 +525,9 @@ impl Board {
             match cell {
                 Cell::Empty => { print!("-"); },
                 Cell::White => { print!("X"); },
-                Cell::Red =>   { print!("O"); }
+                Cell::Red =>   { print!("O"); },
+                Cell::WhiteVictory => { print!("x"); },
+                Cell::RedVictory =>   { print!("o"); }
             }
         }
         println!("\n");
@@ -323,4 +611,212 @@ mod tests {
         b.make_move(3); b.print_self(); assert!(b.winning_moves() == 0); // O blocked X
         b.make_move(4); b.print_self(); assert!(b.winning_moves() == 0); // X blocked O
     }
+
+    #[test]
+    fn test_move_string() {
+        let b = Board::from_moves("").unwrap();
+        assert_eq!(b.counter, 0);
+        assert_eq!(b.to_move_string(), "");
+
+        let b = Board::from_moves("4453").unwrap();
+        assert_eq!(b.moves_played(), &[3, 3, 4, 2]);
+        assert_eq!(b.to_move_string(), "4453");
+
+        let mut expected = Board::new();
+        for col in [3, 3, 4, 2] {
+            expected.make_move(col);
+        }
+        assert_same_board(&b, &expected);
+
+        let full_game = "112233445566771122334455667711223344556677";
+        assert!(Board::from_moves(full_game).is_err());
+        let drawn = "451467634277672635122257755345341336642111";
+        let b = Board::from_moves(drawn).unwrap();
+        assert!(b.is_draw());
+        assert_eq!(b.to_move_string(), drawn);
+    }
+
+    #[test]
+    fn test_move_string_errors() {
+        assert_eq!(
+            Board::from_moves("448").unwrap_err(), 
+            MoveError::InvalidColumn { index: 2, found: '8' });
+        assert_eq!(
+            Board::from_moves("0").unwrap_err(), 
+            MoveError::InvalidColumn { index: 0, found: '0' });
+        assert_eq!(
+            Board::from_moves("44 5").unwrap_err(), 
+            MoveError::InvalidColumn { index: 2, found: ' ' });
+        assert_eq!(
+            Board::from_moves("1111111").unwrap_err(), 
+            MoveError::ColumnFull { index: 6, col: 0 });
+
+        // vertical four for the first player, then an extra move
+        assert_eq!(
+            Board::from_moves("12121214").unwrap_err(), 
+            MoveError::GameOver { index: 7 });
+        assert!(Board::from_moves("1212121").is_ok());
+    }
+
+    #[test]
+    fn test_winning_lines() {
+        // no four yet
+        let b = Board::from_moves("121212").unwrap();
+        assert_eq!(b.winning_mask(Player::White), 0);
+        assert!(b.winning_lines(Player::White).is_empty());
+
+        // vertical in the first column
+        let b = Board::from_moves("1212121").unwrap();
+        assert_eq!(b.winning_mask(Player::White), 0b1111);
+        assert_eq!(b.winning_mask(Player::Red), 0);
+        assert_eq!(b.winning_lines(Player::White), vec![[(0, 0), (0, 1), (0, 2), (0, 3)]]);
+
+        // horizontal along the bottom, five long gives two overlapping lines
+        let b = Board::from_moves("1122334").unwrap();
+        assert_eq!(b.winning_lines(Player::White), vec![[(0, 0), (1, 0), (2, 0), (3, 0)]]);
+        let b = Board::from_moves("23344551").unwrap();
+        assert_eq!(b.winning_mask(Player::Red), 0);
+        let b = Board::from_moves("112233554").unwrap();
+        let lines = b.winning_lines(Player::White);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(b.winning_mask(Player::White).count_ones(), 5);
+
+        // diagonal /
+        let b = Board::from_moves("12233434474").unwrap();
+        assert_eq!(b.winning_lines(Player::White), vec![[(0, 0), (1, 1), (2, 2), (3, 3)]]);
+
+        // diagonal \
+        let b = Board::from_moves("76655454414").unwrap();
+        assert_eq!(b.winning_lines(Player::White), vec![[(3, 3), (4, 2), (5, 1), (6, 0)]]);
+
+        // get_cells starts from the top row
+        let cells = b.get_cells();
+        assert!(cells[(5 - 3)*S_WIDTH + 3] == Cell::WhiteVictory);
+        assert!(cells[(5 - 1)*S_WIDTH + 5] == Cell::WhiteVictory);
+        assert!(cells[(5 - 1)*S_WIDTH + 4] == Cell::White);
+        assert!(cells[5*S_WIDTH + 4] == Cell::Red);
+    }
+
+    #[test]
+    fn test_outcome() {
+        let b = Board::new();
+        assert_eq!(b.side_to_move(), Player::White);
+        assert_eq!(b.outcome(), Outcome::InProgress);
+
+        let b = Board::from_moves("121212").unwrap();
+        assert_eq!(b.side_to_move(), Player::White);
+        assert_eq!(b.outcome(), Outcome::InProgress);
+
+        let b = Board::from_moves("1212121").unwrap();
+        assert_eq!(b.side_to_move(), Player::Red);
+        assert_eq!(b.outcome(), Outcome::Won(Player::White));
+
+        let b = Board::from_moves("71212121").unwrap();
+        assert_eq!(b.side_to_move(), Player::White);
+        assert_eq!(b.outcome(), Outcome::Won(Player::Red));
+
+        let b = Board::from_moves("451467634277672635122257755345341336642111").unwrap();
+        assert_eq!(b.outcome(), Outcome::Draw);
+    }
+
+    fn assert_same_board(a: &Board, b: &Board) {
+        assert_eq!(a.bit_board, b.bit_board);
+        assert_eq!(a.height, b.height);
+        assert_eq!(a.counter, b.counter);
+        assert_eq!(a.moves_played(), b.moves_played());
+    }
+
+    #[test]
+    fn test_undo_move() {
+        let mut b = Board::new();
+        assert!(!b.undo_move());
+
+        b.make_move(3);
+        b.make_move(3);
+        b.make_move(4);
+        assert_eq!(b.moves_played(), &[3, 3, 4]);
+
+        assert!(b.undo_move());
+        assert_eq!(b.moves_played(), &[3, 3]);
+        assert!(b.undo_move());
+        assert!(b.undo_move());
+        assert!(!b.undo_move());
+        assert_same_board(&b, &Board::new());
+    }
+
+    #[test]
+    fn test_mirror() {
+        let b = Board::from_moves("1123").unwrap();
+        let m = b.mirror();
+        assert_eq!(m.to_move_string(), "7765");
+        assert_eq!(b.mirror_key(), m.hash());
+        assert_eq!(m.mirror_key(), b.hash());
+        assert_eq!(b.canonical_key(), m.canonical_key());
+        assert!(b.is_canonical() != m.is_canonical());
+        assert_eq!(b.column_order(), m.column_order().map(|col| S_WIDTH - 1 - col));
+
+        // symmetric positions are their own mirror
+        let b = Board::from_moves("442266").unwrap();
+        assert_eq!(b.mirror_key(), b.hash());
+        assert!(b.is_canonical());
+
+        quad_rand::srand(7);
+        for _ in 0..100 {
+            let mut b = Board::new();
+            while b.outcome() == Outcome::InProgress {
+                assert_eq!(b.mirror_key(), b.mirror().hash());
+                b.make_move(quad_rand::gen_range(0, S_WIDTH));
+            }
+        }
+    }
+
+    #[test]
+    fn test_non_losing_columns() {
+        quad_rand::srand(9);
+        for _ in 0..200 {
+            let mut b = Board::new();
+            while b.outcome() == Outcome::InProgress && b.winning_moves() == 0 {
+                let next: Vec<usize> = b.get_next_non_losing_boards()
+                    .iter()
+                    .flatten()
+                    .map(|next| *next.moves_played().last().unwrap() as usize)
+                    .collect();
+                let expected: Vec<usize> = COLUMN_ORDER.into_iter().filter(|col| next.contains(col)).collect();
+                let columns: Vec<usize> = b.non_losing_columns().iter().flatten().copied().collect();
+                assert_eq!(columns, expected, "{}", b.to_move_string());
+
+                b.make_move(quad_rand::gen_range(0, S_WIDTH));
+            }
+        }
+    }
+
+    #[test]
+    fn test_make_undo_sequences() {
+        quad_rand::srand(42);
+        for _ in 0..200 {
+            let mut b = Board::new();
+            let mut history = vec![b];
+
+            // play a random game, occasionally stepping back
+            while !b.is_draw() {
+                if b.counter > 0 && quad_rand::gen_range(0, 4) == 0 {
+                    assert!(b.undo_move());
+                    history.pop();
+                    assert_same_board(&b, history.last().unwrap());
+                } else {
+                    let col = quad_rand::gen_range(0, S_WIDTH);
+                    if b.make_move(col) {
+                        history.push(b);
+                    }
+                }
+            }
+
+            // unwind the whole game
+            while let Some(expected) = history.pop() {
+                assert_same_board(&b, &expected);
+                b.undo_move();
+            }
+            assert_same_board(&b, &Board::new());
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::ai::solver::{Score, Solver};
+use crate::board::{Board, Outcome, I_HEIGHT, I_WIDTH, S_WIDTH};
+
+const MAGIC: &[u8; 4] = b"C4BK";
+const VERSION: u8 = 1;
+
+// Solved scores for every position in the first plies of the game. A position
+// and its mirror image share one entry. On disk this is a small header 
+// followed by 8 bytes per position: a 7 byte key and the score.
+#[derive(Debug, Default, PartialEq)]
+pub struct OpeningBook {
+    plies: u8,
+    entries: Vec<(u64, Score)> // sorted by key
+}
+
+impl OpeningBook {
+    pub fn new() -> Self {
+        OpeningBook { plies: 0, entries: Vec::new() }
+    }
+
+    // Solve every unfinished position up to plies moves past the root
+    pub fn generate(root: &Board, plies: u8, solver: &mut Solver) -> Self {
+        let mut scores = HashMap::new();
+        let max_counter = root.counter + plies as i8;
+        let mut stack = vec![*root];
+        while let Some(board) = stack.pop() {
+            if board.outcome() != Outcome::InProgress {
+                continue;
+            }
+
+            let key = board.canonical_key();
+            if scores.contains_key(&key) {
+                continue;
+            }
+            scores.insert(key, solver.solve(&board));
+
+            if board.counter < max_counter {
+                stack.extend(board.get_next_boards());
+            }
+        }
+
+        let mut entries: Vec<(u64, Score)> = scores.into_iter().collect();
+        entries.sort_unstable();
+        OpeningBook { plies: max_counter as u8, entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Number of moves into the game the book covers
+    pub fn plies(&self) -> u8 {
+        self.plies
+    }
+
+    pub fn get(&self, board: &Board) -> Option<Score> {
+        let key = board.canonical_key();
+        self.entries
+            .binary_search_by_key(&key, |(k, _)| *k)
+            .ok()
+            .map(|i| self.entries[i].1)
+    }
+
+    // Best column according to the book, if every move from here is in it
+    pub fn best_move(&self, board: &Board) -> Option<usize> {
+        if board.outcome() != Outcome::InProgress {
+            return None;
+        }
+
+        let mut best: Option<(Score, usize)> = None;
+        for col in board.column_order() {
+            let mut next = *board;
+            if !next.make_move(col) {
+                continue;
+            }
+
+            let score = match next.outcome() {
+                Outcome::Won(_) => (I_WIDTH*I_HEIGHT + 1 - board.counter)/2,
+                Outcome::Draw => 0,
+                Outcome::InProgress => -self.get(&next)?,
+            };
+
+            if best.is_none_or(|(s, _)| score > s) {
+                best = Some((score, col));
+            }
+        }
+
+        debug_assert!(best.is_none_or(|(_, col)| col < S_WIDTH));
+        best.map(|(_, col)| col)
+    }
+
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION, self.plies])?;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for (key, score) in &self.entries {
+            writer.write_all(&key.to_le_bytes()[..7])?;
+            writer.write_all(&score.to_le_bytes())?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC || header[4] != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an opening book"));
+        }
+
+        let plies = header[5];
+        let count = u32::from_le_bytes(header[6..10].try_into().unwrap()) as usize;
+        // the count isn't trusted until that many entries have been read, 
+        // so a corrupt header fails to read instead of allocating gigabytes
+        let mut entries = Vec::with_capacity(count.min(1 << 16));
+        let mut entry = [0; 8];
+        for _ in 0..count {
+            reader.read_exact(&mut entry)?;
+            let mut key = [0; 8];
+            key[..7].copy_from_slice(&entry[..7]);
+            entries.push((u64::from_le_bytes(key), entry[7] as i8));
+        }
+
+        if !entries.windows(2).all(|w| w[0].0 < w[1].0) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "opening book is not sorted"));
+        }
+
+        Ok(OpeningBook { plies, entries })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // late enough in a game that the solver is quick
+    const ROOT: &str = "4453635526322115177665";
+
+    #[test]
+    fn test_generate() {
+        let root = Board::from_moves(ROOT).unwrap();
+        let mut solver = Solver::new();
+        let book = OpeningBook::generate(&root, 2, &mut solver);
+        assert!(!book.is_empty());
+        assert_eq!(book.plies(), root.counter as u8 + 2);
+
+        assert_eq!(book.get(&root), Some(solver.solve(&root)));
+        for next in root.get_next_boards() {
+            if next.outcome() == Outcome::InProgress {
+                assert_eq!(book.get(&next), Some(solver.solve(&next)));
+                assert_eq!(book.get(&next.mirror()), book.get(&next));
+            }
+        }
+
+        // the book move is one of the best moves
+        let scores = solver.column_scores(&root);
+        let best = scores.iter().flatten().max().unwrap();
+        let col = book.best_move(&root).unwrap();
+        assert_eq!(scores[col].as_ref(), Some(best));
+
+        // nothing past the end of the book
+        let mut deep = root;
+        for col in [0, 0, 0] {
+            deep.make_move(col);
+        }
+        assert_eq!(book.get(&deep), None);
+    }
+
+    #[test]
+    fn test_read_write() {
+        let root = Board::from_moves(ROOT).unwrap();
+        let book = OpeningBook::generate(&root, 1, &mut Solver::new());
+
+        let mut bytes = Vec::new();
+        book.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 10 + 8*book.len());
+        assert_eq!(OpeningBook::read(bytes.as_slice()).unwrap(), book);
+
+        // a count past the end of the file
+        let mut truncated = bytes.clone();
+        truncated[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(OpeningBook::read(truncated.as_slice()).is_err());
+
+        bytes[0] = b'X';
+        assert!(OpeningBook::read(bytes.as_slice()).is_err());
+        assert!(OpeningBook::read(&bytes[..5]).is_err());
+    }
+}
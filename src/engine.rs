@@ -0,0 +1,243 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+
+use crate::ai::stats::SearchStats;
+use quad_rand::RandomRange;
+
+use crate::board::{Board, Outcome, S_WIDTH};
+use crate::player::Player;
+
+// Anything that can pick a column to play: the AIs, or a person at the GUI.
+pub trait Engine: Send {
+    fn choose_move(&mut self, board: &Board) -> usize;
+
+    // Incremental version of choose_move for callers that can't block, like 
+    // the web build which has no threads. Call it with the same board until 
+    // it returns the column.
+    fn step(&mut self, board: &Board) -> Option<usize> {
+        Some(self.choose_move(board))
+    }
+
+    // Throw away a move started with step()
+    fn cancel(&mut self) {}
+
+    // Setting the flag from another thread makes choose_move return early, 
+    // with a move that should be ignored
+    fn set_stop_signal(&mut self, _stop: Arc<AtomicBool>) {}
+
+    // Interactive players are not ready until their input has arrived, the 
+    // AIs always are.
+    fn ready(&mut self, _board: &Board) -> bool {
+        true
+    }
+
+    fn is_human(&self) -> bool {
+        false
+    }
+
+    // Column picked by a person through the GUI. Ignored by the AIs.
+    fn input(&mut self, _col: usize) {}
+
+    // Columns the engine expects to be played after its last move, starting
+    // with that move. Empty for engines that don't look ahead.
+    fn principal_variation(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    // Counters from the engine's last search, if it searches
+    fn stats(&self) -> Option<SearchStats> {
+        None
+    }
+
+    // Keep what the engine has learned for a later game. Only engines with a
+    // transposition table have anything to keep.
+    fn save(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn load(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Columns as people number them, e.g. "4 4 3 5"
+pub fn format_line(moves: &[u8]) -> String {
+    moves.iter()
+        .map(|col| (col + 1).to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// A person playing through the GUI, which passes in the clicked column 
+pub struct Human {
+    column: Option<usize>
+}
+
+impl Default for Human {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Human {
+    pub fn new() -> Self {
+        Human { column: None }
+    }
+}
+
+impl Engine for Human {
+    fn choose_move(&mut self, _board: &Board) -> usize {
+        self.column.take().expect("choose_move called before the human was ready")
+    }
+
+    fn ready(&mut self, _board: &Board) -> bool {
+        self.column.is_some()
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+
+    fn input(&mut self, col: usize) {
+        self.column = Some(col);
+    }
+}
+
+// Play a game between two engines, white first, and return the final board.
+pub fn play(white: &mut dyn Engine, red: &mut dyn Engine) -> Board {
+    play_from(&Board::new(), white, red)
+}
+
+// Finish a game started elsewhere
+pub fn play_from(board: &Board, white: &mut dyn Engine, red: &mut dyn Engine) -> Board {
+    let mut board = *board;
+    while board.outcome() == Outcome::InProgress {
+        let col = if board.is_white_turn() {
+            white.choose_move(&board)
+        } else {
+            red.choose_move(&board)
+        };
+
+        assert!(board.make_move(col), "engine played into a full column");
+    }
+
+    board
+}
+
+// Games won, drawn and lost by the first engine of a match
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MatchResult {
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+}
+
+impl MatchResult {
+    pub fn games(&self) -> u32 {
+        self.wins + self.draws + self.losses
+    }
+
+    // Share of the points, counting a draw as half a win
+    pub fn score(&self) -> f32 {
+        if self.games() == 0 {
+            return 0.;
+        }
+
+        (self.wins as f32 + self.draws as f32 / 2.) / self.games() as f32
+    }
+}
+
+// Random moves into a game that is still going and where the side to move 
+// can't win straight away
+pub fn random_opening(plies: i8) -> Board {
+    loop {
+        let mut board = Board::new();
+        while board.counter < plies && board.outcome() == Outcome::InProgress {
+            board.make_move(RandomRange::gen_range(0, S_WIDTH));
+        }
+
+        if board.outcome() == Outcome::InProgress && board.winning_moves() == 0 {
+            return board;
+        }
+    }
+}
+
+// Play pairs of games between two engines. Each pair starts from the same 
+// random opening with the engines swapping colours, so neither gets the 
+// better openings.
+pub fn play_match(engine: &mut dyn Engine, opponent: &mut dyn Engine, pairs: u32, opening: i8) -> MatchResult {
+    let mut result = MatchResult::default();
+    for _ in 0..pairs {
+        let start = random_opening(opening);
+        for engine_is_white in [true, false] {
+            let board = if engine_is_white {
+                play_from(&start, engine, opponent)
+            } else {
+                play_from(&start, opponent, engine)
+            };
+
+            match board.outcome() {
+                Outcome::Won(Player::White) if engine_is_white => result.wins += 1,
+                Outcome::Won(Player::Red) if !engine_is_white => result.wins += 1,
+                Outcome::Won(_) => result.losses += 1,
+                _ => result.draws += 1,
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai::{alpha_beta::AlphaBeta, random::Random};
+    use crate::AIType;
+
+    #[test]
+    fn test_human() {
+        let board = Board::new();
+        let mut human = Human::new();
+        assert!(human.is_human());
+        assert!(!human.ready(&board));
+
+        human.input(3);
+        assert!(human.ready(&board));
+        assert_eq!(human.choose_move(&board), 3);
+        assert!(!human.ready(&board));
+    }
+
+    #[test]
+    fn test_headless_games() {
+        quad_rand::srand(1);
+        let board = play(&mut Random::new(), &mut Random::new());
+        assert!(board.outcome() != Outcome::InProgress);
+
+        // the search should have no trouble with a random opponent from either side
+        let mut alpha_beta = AlphaBeta::with_difficulty(AIType::Easy);
+        let board = play(&mut alpha_beta, &mut Random::new());
+        assert_eq!(board.outcome(), Outcome::Won(Player::White));
+
+        let board = play(&mut Random::new(), &mut alpha_beta);
+        assert_eq!(board.outcome(), Outcome::Won(Player::Red));
+    }
+
+    #[test]
+    fn test_play_match() {
+        quad_rand::srand(2);
+        let board = random_opening(10);
+        assert_eq!(board.counter, 10);
+        assert_eq!(board.outcome(), Outcome::InProgress);
+
+        let mut easy = AlphaBeta::with_difficulty(AIType::Easy);
+        let result = play_match(&mut easy, &mut Random::new(), 5, 4);
+        assert_eq!(result.games(), 10);
+        assert!(result.score() >= 0.8, "{:?}", result);
+
+        let even = MatchResult { wins: 1, draws: 2, losses: 1 };
+        assert_eq!(even.score(), 0.5);
+        assert_eq!(MatchResult::default().score(), 0.);
+    }
+}
@@ -0,0 +1,23 @@
+pub mod cell;
+pub mod player;
+pub mod board;
+pub mod transition_table;
+pub mod ai;
+pub mod engine;
+pub mod book;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AIType {
+    Beginner,
+    Easy,
+    Medium,
+    Hard,
+    Impossible,
+}
+
+// Which search plays at the chosen difficulty
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EngineKind {
+    AlphaBeta,
+    MonteCarlo, // tree search, playing more games out at harder levels
+}
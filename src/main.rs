@@ -1,52 +1,87 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use macroquad::prelude::*;
-mod cell;
-mod ui;
+use connect_four::{AIType, EngineKind, book::OpeningBook, player::Player};
 
-mod transition_table;
-mod board;
+mod ui;
 
 mod scene;
 use scene::{scene_trait::Scene, menu_scene::MenuScene, game_scene::GameScene};
 use scene::scene_id::SceneId::*;
 
-mod ai;
+#[derive(PartialEq)]
+pub enum GameMode {
+    HumanVsAI,
+    HumanVsHuman,
+    AIVsAI,
+}
+
+pub struct Settings {
+    pub mode: GameMode,
+    pub ai: AIType, // the opponent, or Red when two AIs play
+    pub white_ai: AIType, // only used when two AIs play
+    pub engine: EngineKind, // search used by ai
+    pub white_engine: EngineKind, // search used by white_ai
+    pub human: Player, // White always moves first
+    pub book: Option<Arc<OpeningBook>>, // opening book for the strongest AIs
+    pub threads: usize, // for each AI's search, native builds only
+    pub table_file: Option<PathBuf>, // where Impossible keeps its table between games, one per colour
+}
 
+// Look for an opening book written by build-book next to the game. The web
+// build has no file system to read it from.
+fn load_book() -> Option<Arc<OpeningBook>> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
 
-#[derive(PartialEq)]
-pub enum AIType {
-    Beginner,
-    Easy,
-    Medium,
-    Hard,
-    Impossible
+    OpeningBook::load("opening_book.bin").ok().map(Arc::new)
 }
 
 #[macroquad::main("Connect-Four")]
 async fn main() {
     let mut scene = Menu;
-    let mut ai = AIType::Hard;
+    let mut settings = Settings { 
+        mode: GameMode::HumanVsAI, 
+        ai: AIType::Hard, 
+        white_ai: AIType::Hard, 
+        engine: EngineKind::AlphaBeta,
+        white_engine: EngineKind::AlphaBeta,
+        human: Player::White,
+        book: load_book(),
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        // the web build has no file system to keep it in
+        table_file: (!cfg!(target_arch = "wasm32")).then(|| PathBuf::from("transposition_table.bin"))
+    };
     
     let mut menu_scene = MenuScene::new();
     let mut game_scene = GameScene::new();
-    let mut current_scene: &mut dyn Scene = &mut menu_scene;
+    let mut quitting = false;
+
+    // give the game a chance to save before the window closes
+    prevent_quit();
 
     loop {
         clear_background(BLACK);
 
-        let new_scene = current_scene.update(&mut ai);
-        if new_scene != scene {
-            match scene {
-                Menu => {
-                    scene = Game;
-                    current_scene = &mut game_scene;
-                },
-                Game => {
-                    scene = Menu;
-                    current_scene = &mut menu_scene;
-                },
+        let current_scene: &mut dyn Scene = match scene {
+            Menu => &mut menu_scene,
+            Game => &mut game_scene,
+        };
+
+        if quitting {
+            // keep drawing until the AIs' tables are written
+            if !game_scene.is_saving() {
+                break;
             }
+        } else if is_quit_requested() {
+            current_scene.quit();
+            quitting = true;
+        } else {
+            scene = current_scene.update(&mut settings);
         }
 
         next_frame().await
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,22 @@
+// White always moves first and owns bit_board[0], Red owns bit_board[1]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Player {
+    White,
+    Red,
+}
+
+impl Player {
+    pub fn index(self) -> usize {
+        match self {
+            Player::White => 0,
+            Player::Red => 1,
+        }
+    }
+
+    pub fn opponent(self) -> Player {
+        match self {
+            Player::White => Player::Red,
+            Player::Red => Player::White,
+        }
+    }
+}
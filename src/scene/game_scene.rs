@@ -1,11 +1,14 @@
 use macroquad::prelude::*;
 use std::cmp::min;
+use std::path::{Path, PathBuf};
 
-use crate::AIType::{self, *};
-use crate::ui::Button;
-use crate::{ai, board::*};
+use connect_four::{ai, ai::stats::SearchStats, board::*, cell::Cell, player::Player, AIType, EngineKind};
+use connect_four::engine::{format_line, Human};
+use crate::ui::{Button, Slider, cell_color};
+use crate::{GameMode, Settings};
 
 use super::scene_id::SceneId::{self, *};
+use super::seat::Seat;
 use super::scene_trait::Scene;
 
 #[derive(PartialEq)]
@@ -19,18 +22,181 @@ pub enum State {
 pub struct GameScene {
     board: Board,
     state: State,
-    alpha_beta: ai::alpha_beta::AlphaBeta
+    players: Option<[Seat; 2]>, // built from the settings when the game starts
+    saving: Vec<Seat>, // players from earlier games still writing their tables
+    expected: String, // line the AI expects after its last move
+    stats: Option<SearchStats>, // from the AI's last search
+    show_stats: bool,
+
+    // AI vs AI playback
+    paused: bool,
+    move_timer: f32,
+    speed_slider: Slider
 }
 
 impl GameScene {
     pub fn new() -> Self {
+        let mut speed_slider = Slider::new();
+        speed_slider
+            .dimensions(100., 20.)
+            .range(0.5, 10.)
+            .value(2.)
+            .color(GRAY)
+            .handle_color(WHITE);
+
         GameScene {
             board: Board::new(),
             state: State::Active,
-            alpha_beta: ai::alpha_beta::AlphaBeta::new()
+            players: None,
+            saving: Vec::new(),
+            expected: String::new(),
+            stats: None,
+            show_stats: false,
+            paused: false,
+            move_timer: 0.,
+            speed_slider
+        }
+    }
+
+    // Each side keeps its own file, so two AIs don't overwrite each other's
+    fn table_file(path: &Path, player: Player) -> PathBuf {
+        let colour = match player {
+            Player::White => "white",
+            Player::Red => "red",
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        path.with_file_name(format!("{}_{}", stem, colour))
+            .with_extension(path.extension().unwrap_or_default())
+    }
+
+    fn new_players(settings: &Settings, saving: &mut Vec<Seat>) -> [Seat; 2] {
+        let book = settings.book.as_ref();
+        let human = || Seat::new(Box::new(Human::new()));
+        saving.retain(Seat::is_using_file);
+
+        // Impossible keeps its table between games, the others search too
+        // little for it to matter. The table is read once the last game has
+        // finished writing it.
+        let mut engine = |player, kind, ai_type| {
+            let engine = ai::new_engine(kind, ai_type, book, settings.threads);
+            match &settings.table_file {
+                Some(path) if kind == EngineKind::AlphaBeta && ai_type == AIType::Impossible => {
+                    let path = Self::table_file(path, player);
+                    let (earlier, rest) = std::mem::take(saving).into_iter()
+                        .partition(|seat| seat.table_file() == Some(&path));
+                    *saving = rest;
+                    Seat::with_table(engine, path, earlier)
+                },
+                _ => Seat::new(engine),
+            }
+        };
+
+        match settings.mode {
+            GameMode::HumanVsAI if settings.human == Player::White => 
+                [human(), engine(Player::Red, settings.engine, settings.ai)],
+            GameMode::HumanVsAI => 
+                [engine(Player::White, settings.engine, settings.ai), human()],
+            GameMode::HumanVsHuman => 
+                [human(), human()],
+            GameMode::AIVsAI => [
+                engine(Player::White, settings.white_engine, settings.white_ai), 
+                engine(Player::Red, settings.engine, settings.ai)
+            ],
         }
     }
 
+    // Stop any search and save what the AIs learned, which carries on after
+    // this returns
+    fn save_tables(&mut self) {
+        self.cancel_search();
+        if let Some(players) = self.players.as_mut() {
+            players.iter_mut().for_each(Seat::save);
+        }
+    }
+
+    // True until every table has been read or written
+    pub fn is_saving(&self) -> bool {
+        self.players.iter().flatten().chain(&self.saving).any(Seat::is_using_file)
+    }
+
+    fn current_player(&mut self) -> &mut Seat {
+        let players = self.players.as_mut().expect("players are built before the game starts");
+        &mut players[self.board.side_to_move().index()]
+    }
+
+    // Let whoever's turn it is move, if they are ready
+    fn play_turn(&mut self) {
+        let board = self.board;
+        if let Some(col) = self.current_player().poll(&board) {
+            let line = self.current_player().principal_variation();
+            if !line.is_empty() {
+                self.expected = format!("AI expects: {}", format_line(&line));
+            }
+            if let Some(stats) = self.current_player().stats() {
+                self.stats = Some(stats);
+            }
+
+            self.board.make_move(col);
+            self.update_state();
+        }
+    }
+
+    // Stop any AI that is still thinking about the current position
+    fn cancel_search(&mut self) {
+        if let Some(players) = self.players.as_mut() {
+            players.iter_mut().for_each(Seat::cancel);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.cancel_search();
+        self.board.reset();
+        self.expected.clear();
+        self.stats = None;
+        self.state = State::Active;
+        self.paused = false;
+        self.move_timer = 0.;
+    }
+
+    fn update_state(&mut self) {
+        self.state = match self.board.outcome() {
+            Outcome::InProgress => State::Active,
+            Outcome::Won(Player::White) => State::WhiteWon,
+            Outcome::Won(Player::Red) => State::RedWon,
+            Outcome::Draw => State::Draw,
+        };
+    }
+
+    // Numbers from the AI's last search in the top left corner
+    fn draw_stats(&self) {
+        let lines = match &self.stats {
+            Some(stats) => vec![
+                format!("depth: {}", stats.depth_reached),
+                format!("nodes: {}", stats.nodes),
+                format!("time: {:.1?}", stats.elapsed),
+                format!("speed: {:.0} knodes/s", stats.nodes_per_second() / 1000.),
+                format!("tt: {} probes, {:.1}% hits, {} collisions", 
+                    stats.tt_probes, 100. * stats.tt_hit_rate(), stats.tt_collisions),
+                format!("cutoffs: {}", stats.cutoffs),
+                format!("branching factor: {:.2}", stats.branching_factor()),
+            ],
+            None => vec!["no search yet".to_string()],
+        };
+
+        for (i, line) in lines.iter().enumerate() {
+            draw_text(line, 10., 20. + 18.*i as f32, 18., GRAY);
+        }
+    }
+
+    fn get_key_column(&self) -> Option<usize> {
+        let keys = [
+            KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, 
+            KeyCode::Key5, KeyCode::Key6, KeyCode::Key7
+        ];
+
+        keys.iter().position(|key| is_key_pressed(*key))
+    }
+
     fn get_mouse_column(&self, pos: (f32, f32), offset: (f32, f32), d: f32) -> Option<usize> {
         let r = d/2.0;
         if pos.0 >= offset.0 && 
@@ -46,8 +212,11 @@ impl GameScene {
 }
 
 impl Scene for GameScene {
-    fn update(&mut self, ai: &mut AIType) -> SceneId {
+    fn update(&mut self, settings: &mut Settings) -> SceneId {
         let mut target_scene = Game;
+        let human = settings.human;
+        let hot_seat = settings.mode == GameMode::HumanVsHuman;
+        let spectator = settings.mode == GameMode::AIVsAI;
 
         // get diameter of board based on current screen size
         let d = min(
@@ -60,9 +229,19 @@ impl Scene for GameScene {
         let offset_width = (screen_width() - board_width) / 2.;
         let offset_height = (screen_height() - board_height) / 2.;
 
+        if self.players.is_none() {
+            self.players = Some(Self::new_players(settings, &mut self.saving));
+        }
+
         if self.state == State::Active {
+            let human_turn = self.current_player().is_human();
             let mouse_pos = mouse_position();
-            let mouse_col = self.get_mouse_column(mouse_pos, (offset_width, offset_height), d);
+            let mouse_col = if human_turn {
+                self.get_mouse_column(mouse_pos, (offset_width, offset_height), d)
+            } else {
+                None
+            };
+
             if let Some(col_index) = mouse_col {
                 // highlight the column the player is hovering over
                 draw_rectangle(
@@ -74,36 +253,101 @@ impl Scene for GameScene {
 
                 // player input to make a move on the board
                 if is_mouse_button_released(MouseButton::Left) {
-                    self.board.make_move(col_index);
-                    // self.board.print_self();
-                    if self.board.is_game_over(self.board.bit_board[0]) {
-                        self.state = State::WhiteWon;
-                    } else if self.board.is_draw() {
-                        self.state = State::Draw;
-                    }
+                    self.current_player().input(col_index);
                 }
             }
 
-            if self.state == State::Active && !self.board.is_white_turn() {
-                // AI turn to make a move
-                match ai {
-                   Beginner => ai::random::make_move(&mut self.board),
-                   Easy => self.alpha_beta.make_move(&mut self.board, 4, ai),
-                   Medium => self.alpha_beta.make_move(&mut self.board, 10, ai),
-                   Hard => self.alpha_beta.make_move(&mut self.board, 17, ai),
-                   Impossible => self.alpha_beta.make_move(&mut self.board, 30, ai),
+            // the number keys drop a disc without the mouse
+            if human_turn {
+                if let Some(col_index) = self.get_key_column() {
+                    self.current_player().input(col_index);
                 }
+            }
+
+            if hot_seat || spectator {
+                let text = match self.board.side_to_move() {
+                    _ if spectator && self.paused => "Paused",
+                    Player::White => "White's turn",
+                    Player::Red => "Red's turn",
+                };
+
+                draw_text(
+                    text, 
+                    screen_width()/2. - d, 
+                    d, 
+                    40.0, 
+                    WHITE);
+            }
+
+            if !spectator {
+                self.play_turn();
+            } else {
+                // both sides are played by the AI at the chosen speed
+                let moves_per_second = self.speed_slider
+                    .pos(screen_width()/2. + d, screen_height() - screen_height()*0.1 - 45.)
+                    .draw();
 
-                if self.board.is_game_over(self.board.bit_board[1]) {
-                    self.state = State::RedWon;
-                } else if self.board.is_draw() {
-                    self.state = State::Draw;
+                draw_text(
+                    &format!("{:.1} moves/s", moves_per_second), 
+                    screen_width()/2. + d + 110., 
+                    screen_height() - screen_height()*0.1 - 30., 
+                    20., 
+                    WHITE);
+
+                if Button::new()
+                    .pos(screen_width()/2. - 3.*d, screen_height() - screen_height()*0.1 - 45.)
+                    .dimensions(70., 30.)
+                    .color(WHITE)
+                    .hover_color(BLUE)
+                    .text(if self.paused { " Resume" } else { " Pause" }.to_string())
+                    .font_size(20.)
+                    .font_color(BLACK)
+                    .draw() || is_key_pressed(KeyCode::P) || is_key_pressed(KeyCode::Space)
+                {
+                    self.paused = !self.paused;
+                }
+
+                let step = Button::new()
+                    .pos(screen_width()/2. - d, screen_height() - screen_height()*0.1 - 45.)
+                    .dimensions(50., 30.)
+                    .color(if self.paused { WHITE } else { GRAY })
+                    .hover_color(BLUE)
+                    .text(" Step".to_string())
+                    .font_size(20.)
+                    .font_color(BLACK)
+                    .is_active(self.paused)
+                    .draw() || (self.paused && is_key_pressed(KeyCode::N));
+
+                // keep polling a search that has already started
+                self.move_timer += get_frame_time();
+                if self.current_player().is_thinking() {
+                    self.play_turn();
+                } else if step || (!self.paused && self.move_timer >= 1. / moves_per_second) {
+                    self.move_timer = 0.;
+                    self.play_turn();
                 }
             }
+
+            let text = if self.state == State::Active && self.current_player().is_thinking() {
+                "Thinking..."
+            } else {
+                &self.expected
+            };
+
+            draw_text(
+                text, 
+                offset_width, 
+                offset_height - 10., 
+                20.0, 
+                GRAY);
         } else {
             let text = match self.state {
-                State::WhiteWon => "You won!",
-                State::RedWon => "AI won! ",
+                State::WhiteWon if hot_seat || spectator => "White won!",
+                State::RedWon if hot_seat || spectator => "Red won!",
+                State::WhiteWon | State::RedWon => {
+                    let human_won = (self.state == State::WhiteWon) == (human == Player::White);
+                    if human_won { "You won!" } else { "AI won! " }
+                },
                 State::Draw => "Draw!",
                 State::Active => "GameState should not be 'Active' if the game is over. Contact admin."
             };
@@ -116,7 +360,15 @@ impl Scene for GameScene {
                 WHITE);
         }
 
-        // render the board
+        // debug overlay with the last search's numbers
+        if is_key_pressed(KeyCode::D) {
+            self.show_stats = !self.show_stats;
+        }
+        if self.show_stats {
+            self.draw_stats();
+        }
+
+        // render the board, outlining the discs that ended the game
         for (i, cell) in self.board.get_cells().iter().enumerate() {
             let x = (i % S_WIDTH) as f32;
             let y = (i / S_WIDTH) as f32;
@@ -124,12 +376,41 @@ impl Scene for GameScene {
                 x*d + d/2. + offset_width, 
                 y*d + d/2. + offset_height, 
                 d/2.0, 
-                cell.to_color()
+                cell_color(*cell)
             );
+
+            if *cell == Cell::WhiteVictory || *cell == Cell::RedVictory {
+                draw_circle_lines(
+                    x*d + d/2. + offset_width, 
+                    y*d + d/2. + offset_height, 
+                    d/2.0 - 2., 
+                    4., 
+                    GOLD
+                );
+            }
         }
 
-        // render buttons to restart or quit
+        // render buttons to undo, restart, or quit
         if Button::new()
+            .pos(screen_width()/2. - 3.*d, screen_height() - screen_height()*0.1)
+            .dimensions(60., 30.)
+            .color(WHITE)
+            .hover_color(BLUE)
+            .text(" Undo".to_string())
+            .font_size(20.)
+            .font_color(BLACK)
+            .draw() || is_key_pressed(KeyCode::U)
+        {
+            // take back the AI's reply as well so it is a person's turn again
+            self.cancel_search();
+            self.expected.clear();
+            if self.board.undo_move() && !spectator && !self.current_player().is_human() {
+                self.board.undo_move();
+            }
+            self.state = State::Active;
+            self.paused = spectator;
+        }
+        else if Button::new()
             .pos(screen_width()/2. - d, screen_height() - screen_height()*0.1)
             .dimensions(78., 30.)
             .color(WHITE)
@@ -139,8 +420,8 @@ impl Scene for GameScene {
             .font_color(BLACK)
             .draw() || is_key_pressed(KeyCode::R)
         {
-            self.board.reset();
-            self.state = State::Active;
+            self.save_tables();
+            self.reset();
         }
         else if Button::new()
             .pos(screen_width()/2. + d, screen_height() - screen_height()*0.1)
@@ -152,11 +433,16 @@ impl Scene for GameScene {
             .font_color(BLACK)
             .draw() || is_key_pressed(KeyCode::Q)
         {
-            self.board.reset();
-            self.state = State::Active;
+            self.save_tables();
+            self.reset();
+            self.saving.extend(self.players.take().into_iter().flatten());
             target_scene = Menu;
         }
 
         target_scene
     }
+
+    fn quit(&mut self) {
+        self.save_tables();
+    }
 }
\ No newline at end of file
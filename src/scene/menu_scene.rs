@@ -1,7 +1,10 @@
 use macroquad::{prelude::{WHITE, RED, GRAY, BLUE}, window::{screen_width, screen_height}, text::{draw_text, get_text_center}};
 use crate::scene::scene_trait::Scene;
-use crate::AIType::{self, *};
+use connect_four::AIType::{self, *};
+use connect_four::EngineKind;
+use connect_four::player::Player;
 use crate::ui::Button;
+use crate::{GameMode, Settings};
 use super::scene_id::SceneId::{self, *};
 
 pub struct MenuScene {
@@ -11,6 +14,14 @@ pub struct MenuScene {
     medium_button: Button,
     hard_button: Button,
     impossible_button: Button,
+    monte_carlo_button: Button,
+    first_button: Button,
+    second_button: Button,
+    vs_ai_button: Button,
+    hot_seat_button: Button,
+    ai_vs_ai_button: Button,
+    threads_button: Button,
+    max_threads: usize,
 }
 
 impl MenuScene {
@@ -64,6 +75,62 @@ impl MenuScene {
             .text(" Impossible".to_string())
             .font_size(15.)
             .font_color(WHITE);
+
+        let mut monte_carlo_button = Button::new();
+        monte_carlo_button
+            .dimensions(50., 30.)
+            .hover_color(BLUE)
+            .text(" MCTS".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut vs_ai_button = Button::new();
+        vs_ai_button
+            .dimensions(100., 30.)
+            .hover_color(BLUE)
+            .text(" Human vs AI".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut hot_seat_button = Button::new();
+        hot_seat_button
+            .dimensions(120., 30.)
+            .hover_color(BLUE)
+            .text(" Human vs Human".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut ai_vs_ai_button = Button::new();
+        ai_vs_ai_button
+            .dimensions(70., 30.)
+            .hover_color(BLUE)
+            .text(" AI vs AI".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut threads_button = Button::new();
+        threads_button
+            .dimensions(90., 30.)
+            .hover_color(BLUE)
+            .color(GRAY)
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut first_button = Button::new();
+        first_button
+            .dimensions(80., 30.)
+            .hover_color(BLUE)
+            .text(" Play First".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
+
+        let mut second_button = Button::new();
+        second_button
+            .dimensions(100., 30.)
+            .hover_color(BLUE)
+            .text(" Play Second".to_string())
+            .font_size(15.)
+            .font_color(WHITE);
             
         MenuScene {
             play_button,
@@ -71,36 +138,23 @@ impl MenuScene {
             easy_button,
             medium_button,
             hard_button,
-            impossible_button
+            impossible_button,
+            monte_carlo_button,
+            first_button,
+            second_button,
+            vs_ai_button,
+            hot_seat_button,
+            ai_vs_ai_button,
+            threads_button,
+            max_threads: std::thread::available_parallelism().map_or(1, |n| n.get())
         }
     }
 }
 
-impl Scene for MenuScene {
-    fn update(&mut self, ai: &mut AIType) -> SceneId {
-        let w = screen_width();
-        let h = screen_height();
-
-        let button_x = w / 2.;
-        let button_height = h - (h/3.5);
-
-        // draw title
-        let mut center = get_text_center("Connect-Four", None, 60, 1., 0.);
-        draw_text(
-            "Connect-Four", 
-            screen_width()/2. - center.x, 
-            screen_height()/3., 
-            60., 
-            WHITE
-        );
-
-        // draw buttons
-        let mut target_scene = Menu;
-        center = get_text_center(" Play", None, 32, 1., 0.);
-        if self.play_button.pos(w/2. - center.x, h/2. - center.y).draw() {
-            target_scene = Game;
-        }
-
+impl MenuScene {
+    // The difficulty buttons, followed by a toggle between the alpha-beta 
+    // search and MCTS
+    fn draw_difficulty(&mut self, ai: &mut AIType, engine: &mut EngineKind, button_x: f32, button_height: f32) {
         if self.beginner_button
             .pos(button_x-220., button_height)
             .color(if *ai == Beginner { RED } else { GRAY })
@@ -152,7 +206,122 @@ impl Scene for MenuScene {
                 .color(GRAY)
                 .is_active(false)
                 .draw(); 
+        }
+
+        let monte_carlo = *engine == EngineKind::MonteCarlo;
+        if self.monte_carlo_button
+            .pos(button_x + 185., button_height)
+            .color(if monte_carlo { RED } else { GRAY })
+            .is_active(true)
+            .draw()
+        {
+            *engine = if monte_carlo { EngineKind::AlphaBeta } else { EngineKind::MonteCarlo };
+        }
+    }
+}
+
+impl Scene for MenuScene {
+    fn update(&mut self, settings: &mut Settings) -> SceneId {
+        let w = screen_width();
+        let h = screen_height();
+
+        let button_x = w / 2.;
+        let button_height = h - (h/3.5);
+
+        // draw title
+        let mut center = get_text_center("Connect-Four", None, 60, 1., 0.);
+        draw_text(
+            "Connect-Four", 
+            screen_width()/2. - center.x, 
+            screen_height()/3., 
+            60., 
+            WHITE
+        );
+
+        // draw buttons
+        let mut target_scene = Menu;
+        center = get_text_center(" Play", None, 32, 1., 0.);
+        if self.play_button.pos(w/2. - center.x, h/2. - center.y).draw() {
+            target_scene = Game;
+        }
+
+        // choose between playing the AI, another person, or watching two AIs
+        let mode = &mut settings.mode;
+        if self.vs_ai_button
+            .pos(button_x - 170., button_height - 90.)
+            .color(if *mode == GameMode::HumanVsAI { RED } else { GRAY })
+            .is_active(*mode != GameMode::HumanVsAI)
+            .draw()
+        {
+            *mode = GameMode::HumanVsAI;
+        }
+
+        if self.hot_seat_button
+            .pos(button_x - 60., button_height - 90.)
+            .color(if *mode == GameMode::HumanVsHuman { RED } else { GRAY })
+            .is_active(*mode != GameMode::HumanVsHuman)
+            .draw()
+        {
+            *mode = GameMode::HumanVsHuman;
+        }
+
+        if self.ai_vs_ai_button
+            .pos(button_x + 70., button_height - 90.)
+            .color(if *mode == GameMode::AIVsAI { RED } else { GRAY })
+            .is_active(*mode != GameMode::AIVsAI)
+            .draw()
+        {
+            *mode = GameMode::AIVsAI;
+        }
+
+        match settings.mode {
+            GameMode::HumanVsAI => {
+                // choose who moves first
+                let human = &mut settings.human;
+                if self.first_button
+                    .pos(button_x - 90., button_height - 45.)
+                    .color(if *human == Player::White { RED } else { GRAY })
+                    .is_active(*human != Player::White)
+                    .draw()
+                {
+                    *human = Player::White;
+                }
+
+                if self.second_button
+                    .pos(button_x, button_height - 45.)
+                    .color(if *human == Player::Red { RED } else { GRAY })
+                    .is_active(*human != Player::Red)
+                    .draw()
+                {
+                    *human = Player::Red;
+                }
+
+                self.draw_difficulty(&mut settings.ai, &mut settings.engine, button_x, button_height);
+            },
+            GameMode::AIVsAI => {
+                // one row of difficulties for each side
+                draw_text("White", button_x - 280., button_height - 25., 20., WHITE);
+                self.draw_difficulty(&mut settings.white_ai, &mut settings.white_engine, button_x, button_height - 45.);
+                draw_text("Red", button_x - 280., button_height + 20., 20., WHITE);
+                self.draw_difficulty(&mut settings.ai, &mut settings.engine, button_x, button_height);
+            },
+            GameMode::HumanVsHuman => {}
+        }
+
+        // threads for each AI's search, doubling on each click and wrapping 
+        // back to one. The web build has no threads.
+        if !cfg!(target_arch = "wasm32") && settings.mode != GameMode::HumanVsHuman {
+            let threads = &mut settings.threads;
+            if self.threads_button
+                .pos(button_x - 45., button_height + 45.)
+                .text(format!(" Threads: {}", threads))
+                .draw()
+            {
+                *threads = if *threads >= self.max_threads { 1 } else { (*threads * 2).min(self.max_threads) };
+            }
+        }
 
+        if cfg!(target_arch = "wasm32") && settings.mode != GameMode::HumanVsHuman {
             let text = "Impossible AI disabled for web version.";
             center = get_text_center(text, None, 15, 1., 0.);
             draw_text(
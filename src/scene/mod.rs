@@ -1,4 +1,5 @@
 pub mod scene_id;
 pub mod scene_trait;
 pub mod menu_scene;
-pub mod game_scene;
\ No newline at end of file
+pub mod game_scene;
+pub mod seat;
\ No newline at end of file
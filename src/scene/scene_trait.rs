@@ -1,5 +1,9 @@
 use crate::scene::scene_id::SceneId;
+use crate::Settings;
 
 pub trait Scene {
-    fn update(&mut self, ai: &mut crate::AIType) -> SceneId;
+    fn update(&mut self, settings: &mut Settings) -> SceneId;
+
+    // The window is closing
+    fn quit(&mut self) {}
 }
\ No newline at end of file
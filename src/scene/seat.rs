@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+use connect_four::ai::stats::SearchStats;
+use connect_four::board::Board;
+use connect_four::engine::Engine;
+
+// Search running on another thread, which hands the engine back with its move
+type Thinking = (JoinHandle<(Box<dyn Engine>, usize)>, Arc<AtomicBool>);
+
+// Table being read or written on another thread, which hands the engine back
+type FileIo = JoinHandle<Box<dyn Engine>>;
+
+// One side of the board. AIs search on a background thread natively, or a 
+// step per frame on the web, so the window keeps drawing while they think.
+pub struct Seat {
+    engine: Option<Box<dyn Engine>>, // None while a thread has it
+    thinking: Option<Thinking>,
+    stepping: bool,
+    table_file: Option<PathBuf>, // the engine's saved state, if it keeps one
+    file_io: Option<FileIo>
+}
+
+impl Seat {
+    pub fn new(engine: Box<dyn Engine>) -> Self {
+        Seat { engine: Some(engine), thinking: None, stepping: false, table_file: None, file_io: None }
+    }
+
+    // An engine that starts from what it saved in the file last time, if 
+    // there is a file yet, and saves back to it. The file is read on another
+    // thread once the earlier seats that use it have finished writing it.
+    pub fn with_table(mut engine: Box<dyn Engine>, table_file: PathBuf, earlier: Vec<Seat>) -> Self {
+        let path = table_file.clone();
+        let handle = thread::spawn(move || {
+            earlier.into_iter().for_each(Seat::wait);
+            engine.load(&path).ok();
+            engine
+        });
+
+        Seat { engine: None, thinking: None, stepping: false, table_file: Some(table_file), file_io: Some(handle) }
+    }
+
+    // Write the engine's state back to its file on another thread. Call 
+    // cancel() first, the engine is away while it is thinking.
+    pub fn save(&mut self) {
+        let Some(path) = self.table_file.clone() else { return };
+        if let Some(engine) = self.engine.take() {
+            self.file_io = Some(thread::spawn(move || {
+                engine.save(&path).ok();
+                engine
+            }));
+        }
+    }
+
+    pub fn table_file(&self) -> Option<&PathBuf> {
+        self.table_file.as_ref()
+    }
+
+    // True while the table is still being read or written
+    pub fn is_using_file(&self) -> bool {
+        self.file_io.as_ref().is_some_and(|handle| !handle.is_finished())
+    }
+
+    // Block until the table has been read or written
+    fn wait(mut self) {
+        if let Some(handle) = self.file_io.take() {
+            handle.join().expect("table thread panicked");
+        }
+    }
+
+    pub fn is_human(&self) -> bool {
+        self.engine.as_ref().is_some_and(|e| e.is_human())
+    }
+
+    pub fn is_thinking(&self) -> bool {
+        self.thinking.is_some() || self.stepping || self.file_io.is_some()
+    }
+
+    pub fn input(&mut self, col: usize) {
+        if let Some(engine) = self.engine.as_mut() {
+            engine.input(col);
+        }
+    }
+
+    // Called every frame on this seat's turn, returns the column once the 
+    // move has been decided
+    pub fn poll(&mut self, board: &Board) -> Option<usize> {
+        if let Some(handle) = &self.file_io {
+            if !handle.is_finished() {
+                return None;
+            }
+
+            let handle = self.file_io.take().unwrap();
+            self.engine = Some(handle.join().expect("table thread panicked"));
+        }
+
+        if let Some((handle, _)) = &self.thinking {
+            if !handle.is_finished() {
+                return None;
+            }
+
+            let (handle, _) = self.thinking.take().unwrap();
+            let (engine, col) = handle.join().expect("search thread panicked");
+            self.engine = Some(engine);
+            return Some(col);
+        }
+
+        let engine = self.engine.as_mut().unwrap();
+        if !engine.ready(board) {
+            None
+        } else if engine.is_human() {
+            Some(engine.choose_move(board))
+        } else if cfg!(target_arch = "wasm32") {
+            let col = engine.step(board);
+            self.stepping = col.is_none();
+            col
+        } else {
+            let stop = Arc::new(AtomicBool::new(false));
+            let mut engine = self.engine.take().unwrap();
+            engine.set_stop_signal(stop.clone());
+
+            let board = *board;
+            let handle = thread::spawn(move || {
+                let col = engine.choose_move(&board);
+                (engine, col)
+            });
+
+            self.thinking = Some((handle, stop));
+            None
+        }
+    }
+
+    // What the AI expected after its last move, empty while it is thinking
+    pub fn principal_variation(&self) -> Vec<u8> {
+        self.engine.as_ref().map_or(Vec::new(), |e| e.principal_variation())
+    }
+
+    pub fn stats(&self) -> Option<SearchStats> {
+        self.engine.as_ref().and_then(|e| e.stats())
+    }
+
+    // Stop thinking and throw the move away
+    pub fn cancel(&mut self) {
+        if let Some((handle, stop)) = self.thinking.take() {
+            stop.store(true, Ordering::Relaxed);
+            let (engine, _) = handle.join().expect("search thread panicked");
+            self.engine = Some(engine);
+        }
+
+        if let Some(engine) = self.engine.as_mut() {
+            engine.cancel();
+        }
+        self.stepping = false;
+    }
+}
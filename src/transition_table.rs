@@ -1,49 +1,455 @@
-#[derive(Copy, Clone)]
-struct Entry {
-    key: u64,
-    val: i8
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// How the stored value relates to the real value of the position
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    Lower, // search failed high, real value is at least this
+    Upper, // search failed low, real value is at most this
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Entry {
+    pub key: u64,
+    pub val: i8,
+    pub depth: u8,
+    pub bound: Bound,
+    pub best_move: u8
 }
 
 impl Entry {
     pub fn new() -> Self {
-        Entry { key: 0, val: 0 }
+        Entry { key: 0, val: 0, depth: 0, bound: Bound::Exact, best_move: 0 }
+    }
+}
+
+impl Default for Entry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bound {
+    fn from_bits(bits: u64) -> Self {
+        match bits {
+            0 => Bound::Exact,
+            1 => Bound::Lower,
+            _ => Bound::Upper,
+        }
+    }
+}
+
+/*
+Each slot is one word:
+
+  bits  0-7   value
+  bits  8-13  depth
+  bits 14-15  bound
+  bits 16-18  best move
+  bits 19-63  the low 45 bits of the key
+
+Keys are at most 49 bits and the slot is the key modulo a prime, so the slot
+and the low bits together still tell every position apart as long as the
+table has more than 2^4 slots.
+*/
+const KEY_SHIFT: u32 = 19;
+const KEY_MASK: u64 = (1 << (64 - KEY_SHIFT)) - 1;
+const MIN_ENTRIES: usize = 17;
+
+const MAGIC: &[u8; 4] = b"C4TT";
+const VERSION: u8 = 2; // 2: keys are canonical_key()
+
+impl Entry {
+    fn pack(&self) -> u64 {
+        (self.val as u8 as u64)
+            | ((self.depth as u64 & 0x3F) << 8)
+            | ((self.bound as u64) << 14)
+            | ((self.best_move as u64 & 0x7) << 16)
+            | ((self.key & KEY_MASK) << KEY_SHIFT)
+    }
+
+    fn unpack(key: u64, slot: u64) -> Self {
+        Entry {
+            key,
+            val: slot as u8 as i8,
+            depth: ((slot >> 8) & 0x3F) as u8,
+            bound: Bound::from_bits((slot >> 14) & 0x3),
+            best_move: ((slot >> 16) & 0x7) as u8
+        }
+    }
+}
+
+// Number of slots, rounded down to a prime when the table is made
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TableSize {
+    Entries(usize),
+    Megabytes(usize),
+}
+
+impl TableSize {
+    // The browser gets a much smaller table, since it has far less memory
+    // to spare and searches far fewer nodes
+    pub fn default_size() -> Self {
+        if cfg!(target_arch = "wasm32") {
+            TableSize::Megabytes(16)
+        } else {
+            TableSize::Entries(10411033)
+        }
+    }
+
+    fn entries(&self) -> usize {
+        let entries = match *self {
+            TableSize::Entries(entries) => entries,
+            TableSize::Megabytes(mb) => mb * (1 << 20) / std::mem::size_of::<u64>(),
+        };
+
+        prev_prime(entries.max(MIN_ENTRIES))
     }
 }
 
-const TABLE_MAX_SIZE: usize = 10411033; // large prime numbers
+// FNV-1a over everything after the magic, to catch files that were cut short
+// or changed
+struct Checksum(u64);
 
+impl Checksum {
+    fn new() -> Self {
+        Checksum(0xcbf29ce484222325)
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn is_prime(n: usize) -> bool {
+    n >= 2 && (2..).take_while(|d| d*d <= n).all(|d| !n.is_multiple_of(d))
+}
+
+fn prev_prime(n: usize) -> usize {
+    (2..=n).rev().find(|n| is_prime(*n)).unwrap()
+}
+
+// Shared between search threads without locks, since every slot is a single
+// atomic word. Nothing is allocated until the first entry is stored.
 pub struct TransitionTable {
-    table: Vec<Entry>
+    len: usize,
+    table: OnceLock<Vec<AtomicU64>>
+}
+
+impl Default for TransitionTable {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl TransitionTable {
     pub fn new() -> Self {
-        TransitionTable { table: vec![Entry::new(); TABLE_MAX_SIZE] }
+        Self::with_size(TableSize::default_size())
+    }
+
+    pub fn with_size(size: TableSize) -> Self {
+        TransitionTable { len: size.entries(), table: OnceLock::new() }
     }
 
-    pub fn reset(&mut self) {
-        self.table.iter_mut().for_each(|e| {
-            e.key = 0;
-            e.val = 0;
-        });
+    // Number of slots
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_allocated(&self) -> bool {
+        self.table.get().is_some()
+    }
+
+    fn slots(&self) -> &[AtomicU64] {
+        self.table.get_or_init(|| (0..self.len).map(|_| AtomicU64::new(0)).collect())
+    }
+
+    pub fn reset(&self) {
+        if let Some(table) = self.table.get() {
+            table.iter().for_each(|slot| slot.store(0, Ordering::Relaxed));
+        }
     }
 
     fn index(&self, key: u64) ->  usize {
-        key as usize % TABLE_MAX_SIZE
+        key as usize % self.len
+    }
+
+    fn load_slot(&self, key: u64) -> u64 {
+        self.table.get().map_or(0, |table| table[self.index(key)].load(Ordering::Relaxed))
     }
 
-    pub fn get(&self, key: u64) -> Option<i8> {
-        let i = self.index(key);
-        if self.table[i].key == key {
-            Some(self.table[i].val)
+    // Key 0 is the empty board, which is never stored, and stored entries
+    // always have a depth, so empty slots never match
+    pub fn get(&self, key: u64) -> Option<Entry> {
+        let slot = self.load_slot(key);
+        if key != 0 && slot != 0 && slot >> KEY_SHIFT == key & KEY_MASK {
+            Some(Entry::unpack(key, slot))
         } else {
             None
         }
     }
 
-    pub fn set(&mut self, key: u64, val: i8) {
-        let i = self.index(key);
-        self.table[i].key = key;
-        self.table[i].val = val;
+    // Whether the key's slot holds any position, used to count collisions
+    pub fn occupied(&self, key: u64) -> bool {
+        self.load_slot(key) != 0
+    }
+
+    pub fn set(&self, key: u64, val: i8, depth: u8, bound: Bound, best_move: u8) {
+        let slot = Entry { key, val, depth, bound, best_move }.pack();
+        self.slots()[self.index(key)].store(slot, Ordering::Relaxed);
+    }
+
+    // The occupied slots as (index, slot) pairs
+    fn occupied_slots(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.table.get()
+            .into_iter()
+            .flatten()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .enumerate()
+            .filter(|(_, slot)| *slot != 0)
     }
-}
\ No newline at end of file
+
+    // Binary format: the magic "C4TT", a version byte, the number of slots 
+    // and of occupied slots as u64, then a u32 index and the u64 slot for each
+    // occupied slot, and finally the checksum. Everything is little endian.
+    pub fn write<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_slots(writer, |_| true)
+    }
+
+    // Like write(), but only the exact values. Bounds depend on the window 
+    // they were searched with, so they are rarely any use to a later game and
+    // make up most of the table.
+    pub fn write_exact<W: Write>(&self, writer: W) -> io::Result<()> {
+        self.write_slots(writer, |slot| Bound::from_bits((slot >> 14) & 0x3) == Bound::Exact)
+    }
+
+    fn write_slots<W: Write>(&self, mut writer: W, keep: impl Fn(u64) -> bool) -> io::Result<()> {
+        if self.len > u32::MAX as usize {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "table too large to save"));
+        }
+
+        writer.write_all(MAGIC)?;
+        let mut checksum = Checksum::new();
+        let mut write = |bytes: &[u8]| {
+            checksum.update(bytes);
+            writer.write_all(bytes)
+        };
+
+        // one pass over the table, so helper threads still writing to it 
+        // can't make the count disagree with the entries
+        let entries: Vec<(usize, u64)> = self.occupied_slots().filter(|(_, slot)| keep(*slot)).collect();
+
+        write(&[VERSION])?;
+        write(&(self.len as u64).to_le_bytes())?;
+        write(&(entries.len() as u64).to_le_bytes())?;
+        for (index, slot) in entries {
+            write(&(index as u32).to_le_bytes())?;
+            write(&slot.to_le_bytes())?;
+        }
+
+        let checksum = checksum.0;
+        writer.write_all(&checksum.to_le_bytes())?;
+        writer.flush()
+    }
+
+    // Everything is checked before the table is made, so a damaged file 
+    // gives an error rather than a huge allocation
+    pub fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a transposition table"));
+        }
+
+        let mut header = [0; 17];
+        reader.read_exact(&mut header)?;
+        if header[0] != VERSION {
+            return Err(invalid("unsupported transposition table version"));
+        }
+
+        let len = u64::from_le_bytes(header[1..9].try_into().unwrap());
+        let count = u64::from_le_bytes(header[9..17].try_into().unwrap());
+        if len > u32::MAX as u64 || len < MIN_ENTRIES as u64 || !is_prime(len as usize) {
+            return Err(invalid("transposition table size is not one this program writes"));
+        }
+        if count > len {
+            return Err(invalid("transposition table has more entries than slots"));
+        }
+
+        // the entries and the checksum should be all that is left
+        let size = count * 12 + 8;
+        let mut body = Vec::new();
+        reader.take(size + 1).read_to_end(&mut body)?;
+        if body.len() as u64 != size {
+            return Err(invalid("transposition table does not match its size"));
+        }
+
+        let (entries, stored) = body.split_at(body.len() - 8);
+        let mut checksum = Checksum::new();
+        checksum.update(&header);
+        checksum.update(entries);
+        if u64::from_le_bytes(stored.try_into().unwrap()) != checksum.0 {
+            return Err(invalid("transposition table checksum does not match"));
+        }
+
+        let entries: Vec<(usize, u64)> = entries.chunks_exact(12)
+            .map(|entry| (
+                u32::from_le_bytes(entry[..4].try_into().unwrap()) as usize,
+                u64::from_le_bytes(entry[4..].try_into().unwrap())
+            ))
+            .collect();
+        let len = len as usize;
+        if entries.iter().any(|(index, _)| *index >= len) {
+            return Err(invalid("transposition table entry out of range"));
+        }
+
+        let table = TransitionTable { len, table: OnceLock::new() };
+        for (index, slot) in entries {
+            table.slots()[index].store(slot, Ordering::Relaxed);
+        }
+
+        Ok(table)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn save_exact<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_exact(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_set() {
+        let table = TransitionTable::new();
+        assert!(table.get(0).is_none());
+        assert!(table.get(12345).is_none());
+
+        table.set(12345, -3, 7, Bound::Lower, 4);
+        let entry = table.get(12345).unwrap();
+        assert_eq!(entry.val, -3);
+        assert_eq!(entry.depth, 7);
+        assert_eq!(entry.bound, Bound::Lower);
+        assert_eq!(entry.best_move, 4);
+
+        // a colliding key replaces the old entry
+        table.set(12345 + table.len() as u64, 2, 1, Bound::Exact, 0);
+        assert!(table.get(12345).is_none());
+        assert!(table.occupied(12345));
+
+        table.reset();
+        assert!(table.get(12345 + table.len() as u64).is_none());
+        assert!(!table.occupied(12345));
+    }
+
+    #[test]
+    fn test_packing() {
+        // the largest key a board can have, with every field at its limit
+        let key = (1 << 49) - 1;
+        let table = TransitionTable::with_size(TableSize::Entries(100));
+        table.set(key, -21, 42, Bound::Upper, 6);
+        let entry = table.get(key).unwrap();
+        assert_eq!((entry.val, entry.depth, entry.bound, entry.best_move), (-21, 42, Bound::Upper, 6));
+
+        // same slot, different position
+        let other = key - table.len() as u64;
+        assert_eq!(table.index(other), table.index(key));
+        assert!(table.get(other).is_none());
+    }
+
+    #[test]
+    fn test_read_write() {
+        let table = TransitionTable::with_size(TableSize::Entries(1000));
+        let mut bytes = Vec::new();
+        table.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 + 1 + 8 + 8 + 8);
+
+        table.set(12345, -3, 7, Bound::Lower, 4);
+        table.set(678, 5, 30, Bound::Exact, 2);
+        let mut bytes = Vec::new();
+        table.write(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), 4 + 1 + 8 + 8 + 2*12 + 8);
+
+        let loaded = TransitionTable::read(bytes.as_slice()).unwrap();
+        assert_eq!(loaded.len(), table.len());
+        for key in [12345, 678] {
+            let (a, b) = (loaded.get(key).unwrap(), table.get(key).unwrap());
+            assert_eq!((a.val, a.depth, a.bound, a.best_move), (b.val, b.depth, b.bound, b.best_move));
+        }
+
+        // only the exact value is kept
+        let mut exact = Vec::new();
+        table.write_exact(&mut exact).unwrap();
+        let loaded = TransitionTable::read(exact.as_slice()).unwrap();
+        assert!(loaded.get(12345).is_none());
+        assert_eq!(loaded.get(678).unwrap().val, 5);
+
+        // any damage is caught
+        let mut changed = bytes.clone();
+        changed[30] ^= 1;
+        assert!(TransitionTable::read(changed.as_slice()).is_err());
+        assert!(TransitionTable::read(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut version = bytes.clone();
+        version[4] = VERSION + 1;
+        assert!(TransitionTable::read(version.as_slice()).is_err());
+
+        // sizes are checked before anything is allocated
+        for len in [u64::MAX, u32::MAX as u64 + 15, 1000] {
+            let mut size = bytes.clone();
+            size[5..13].copy_from_slice(&len.to_le_bytes());
+            assert!(TransitionTable::read(size.as_slice()).is_err(), "{}", len);
+        }
+        let mut count = bytes.clone();
+        count[13..21].copy_from_slice(&u32::MAX.to_le_bytes().repeat(2));
+        assert!(TransitionTable::read(count.as_slice()).is_err());
+        let mut extra = bytes.clone();
+        extra.push(0);
+        assert!(TransitionTable::read(extra.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_size() {
+        assert_eq!(TransitionTable::with_size(TableSize::Entries(100)).len(), 97);
+        assert_eq!(TransitionTable::with_size(TableSize::Entries(10411033)).len(), 10411033);
+        assert_eq!(TransitionTable::with_size(TableSize::Megabytes(1)).len(), 131071);
+        assert_eq!(TransitionTable::with_size(TableSize::Entries(0)).len(), MIN_ENTRIES);
+    }
+
+    #[test]
+    fn test_lazy_allocation() {
+        let table = TransitionTable::new();
+        assert!(!table.is_allocated());
+
+        // looking things up doesn't need the memory
+        assert!(table.get(12345).is_none());
+        table.reset();
+        assert!(!table.is_allocated());
+
+        table.set(12345, 1, 1, Bound::Exact, 0);
+        assert!(table.is_allocated());
+    }
+}
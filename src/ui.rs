@@ -1,4 +1,15 @@
 use macroquad::prelude::*;
+use connect_four::cell::Cell;
+
+pub fn cell_color(cell: Cell) -> Color {
+    match cell {
+        Cell::Empty => Color::new(0.75, 0.0, 0.05, 0.2),
+        Cell::White => WHITE,
+        Cell::Red => RED,
+        Cell::WhiteVictory => Color::new(0.8, 0.8, 0.8, 0.9),
+        Cell::RedVictory => Color::new(1.0, 0.3, 0.3, 0.5),
+    }
+}
 
 pub struct Button {
     x: f32,
@@ -82,11 +93,10 @@ impl Button {
             p.1 >= self.y && 
             p.1 <= self.y+self.h;
 
-        let rec_color = 
-            if self.hover_color.is_some() && mouse_in_bounds 
-                { self.hover_color.unwrap() } 
-            else 
-                { self.color };
+        let rec_color = match self.hover_color {
+            Some(hover_color) if mouse_in_bounds => hover_color,
+            _ => self.color
+        };
 
         draw_rectangle(
             self.x,
@@ -96,9 +106,9 @@ impl Button {
             rec_color
         );
 
-        if self.text.is_some() {
+        if let Some(text) = &self.text {
             draw_text(
-                self.text.as_ref().unwrap().as_str(),
+                text.as_str(),
                 self.x,
                 self.y + self.h/2.,
                 self.font_size,
@@ -114,3 +124,108 @@ impl Button {
         self.clicked
     }
 }
+
+pub struct Slider {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    min: f32,
+    max: f32,
+    value: f32,
+    color: Color,
+    handle_color: Color,
+    dragging: bool
+}
+
+impl Slider {
+    pub fn new() -> Self {
+        Self {
+            x: 0.,
+            y: 0.,
+            w: 0.,
+            h: 0.,
+            min: 0.,
+            max: 1.,
+            value: 0.,
+            color: GRAY,
+            handle_color: WHITE,
+            dragging: false
+        }
+    }
+
+    pub fn pos(&mut self, x: f32, y: f32) -> &mut Self {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    pub fn dimensions(&mut self, w: f32, h: f32) -> &mut Self {
+        self.w = w;
+        self.h = h;
+        self
+    }
+
+    pub fn range(&mut self, min: f32, max: f32) -> &mut Self {
+        self.min = min;
+        self.max = max;
+        self.value = self.value.clamp(min, max);
+        self
+    }
+
+    pub fn value(&mut self, value: f32) -> &mut Self {
+        self.value = value.clamp(self.min, self.max);
+        self
+    }
+
+    pub fn color(&mut self, color: Color) -> &mut Self {
+        self.color = color;
+        self
+    }
+
+    pub fn handle_color(&mut self, color: Color) -> &mut Self {
+        self.handle_color = color;
+        self
+    }
+
+    // Draw the slider and return its value, which follows the mouse while the
+    // handle is being dragged.
+    pub fn draw(&mut self) -> f32 {
+        let p = mouse_position();
+        let mouse_in_bounds = 
+            p.0 >= self.x && 
+            p.0 <= self.x+self.w && 
+            p.1 >= self.y && 
+            p.1 <= self.y+self.h;
+
+        if is_mouse_button_pressed(MouseButton::Left) && mouse_in_bounds {
+            self.dragging = true;
+        } else if !is_mouse_button_down(MouseButton::Left) {
+            self.dragging = false;
+        }
+
+        if self.dragging {
+            let t = ((p.0 - self.x) / self.w).clamp(0., 1.);
+            self.value = self.min + t*(self.max - self.min);
+        }
+
+        draw_rectangle(
+            self.x,
+            self.y + self.h/2. - 2.,
+            self.w,
+            4.,
+            self.color
+        );
+
+        let t = (self.value - self.min) / (self.max - self.min);
+        draw_rectangle(
+            self.x + t*self.w - 4.,
+            self.y,
+            8.,
+            self.h,
+            self.handle_color
+        );
+
+        self.value
+    }
+}